@@ -1,4 +1,9 @@
 fn main() {
+    // the rust-parser only build does not need the cpp stage at all
+    if std::env::var_os("CARGO_FEATURE_FFI_PARSER").is_none() {
+        return;
+    }
+
     println!("cargo:rustc-link-search=native=.");
     println!("cargo:rustc-link-lib=static=cpp");
     println!("cargo:rustc-link-lib=static=gumbo");
@@ -1,12 +1,77 @@
 #pragma once
 
+#include <cstddef>
 #include <gumbo.h>
+#include <nlohmann/json_fwd.hpp>
+#include <initializer_list>
 #include <string>
+#include <vector>
+
+// return codes shared by every extern "C" parser
+// keep in sync with ParseFailure in src/parser/mod.rs
+enum ParseCode : int {
+	PARSE_OK = 0,
+	PARSE_GUMBO_FAILED = 1,
+	PARSE_MAIN_NOT_FOUND = 2,
+	PARSE_CANONICAL_URL_NOT_FOUND = 3,
+	PARSE_TITLE_NOT_FOUND = 4,
+	PARSE_POST_TIME_NOT_FOUND = 5,
+	PARSE_WORK_ID_NOT_FOUND = 6,
+	PARSE_TAG_LIST_NOT_FOUND = 7,
+	PARSE_PAGENAVI_NOT_FOUND = 8, // unused, a missing wp-pagenavi is a single page
+	PARSE_PAGE_COUNT_NOT_FOUND = 9,
+	PARSE_POST_TIME_UNPARSEABLE = 10,
+	PARSE_EXCEPTION = 99,
+};
+
+// diagnostics are handed to rust (tracing) through a callback registered at startup
+// levels follow tracing: 1 error, 2 warn, 3 info, 4 debug, 5 trace
+enum LogLevel : int {
+	LOG_ERROR = 1,
+	LOG_WARN = 2,
+	LOG_INFO = 3,
+	LOG_DEBUG = 4,
+	LOG_TRACE = 5,
+};
+typedef void (*log_callback_t)(int level, const char* parser, size_t parser_len, const char* msg, size_t msg_len);
+extern "C" void set_log_callback(log_callback_t callback);
+void log_msg(int level, const char* parser, const std::string& msg);
+
+// utc offset (minutes) of the wall time written in div#post-time, JST (+540) unless set at startup
+extern "C" void set_meta_utc_offset(int minutes);
 
 extern "C" void free_string(char* s);
+// malloc'd copy handed to rust together with its length, released by free_char
+char* copy_result(const std::string& s, size_t* len);
 bool has_id(GumboElement* el,  const char* id);
+// class is a whitespace separated token list, so `post-list clearfix` has the class `post-list`
 bool has_class(GumboElement* el,  const char* _class);
+
+// one step of a tiny css-like selector, unset fields match anything
+// e.g. {.tag = GUMBO_TAG_DIV, .cls = "post-list", .child = true} is `> div.post-list`
+struct Selector {
+	GumboTag tag = GUMBO_TAG_UNKNOWN;
+	const char* id = nullptr;
+	const char* cls = nullptr;
+	// direct child of the previous step (or of the root) instead of any descendant
+	bool child = false;
+};
+bool matches(GumboNode* node, const Selector& sel);
+// every element matching the chain below root (root excluded),
+// ordered by the match of the previous step first, then by document order
+// e.g. select_all(root, {{.tag = GUMBO_TAG_DIV, .id = "container"}, {.tag = GUMBO_TAG_DIV, .id = "main", .child = true}})
+std::vector<GumboNode*> select_all(GumboNode* root, std::initializer_list<Selector> chain);
+GumboNode* select_first(GumboNode* root, std::initializer_list<Selector> chain);
+GumboNode* select_last(GumboNode* root, std::initializer_list<Selector> chain);
 void inner_text(GumboNode* node,  std::string& out);
+// empty string ---> null
+nlohmann::json string_or_null(const std::string& s);
+// strips the characters gumbo treats as whitespace
+std::string trim(const std::string& s);
+// rj number of a work url, e.g. https://example.com/rj01234567/ ---> rj01234567
+bool find_id_from_url(const std::string& url, std::string& id);
+// resolves href against an absolute base url, dot segments are kept as they are
+std::string resolve_url(const std::string& base, const std::string& href);
 
 struct Tag {
 	std::string name;
@@ -4,95 +4,108 @@
 #include <string>
 #include <vector>
 #include <gumbo_search_lib.hpp>
-#include <iostream>
 
-// find main!
-void walk_through(GumboNode* node, GumboNode*& main) {
-	if (node->type != GUMBO_NODE_ELEMENT || main) {
-		return;
+// <base href> wins over <link rel="canonical">, as in browsers
+std::string find_base_url(GumboNode* root) {
+	for (GumboNode* node : select_all(root, {{.tag = GUMBO_TAG_BASE}})) {
+		GumboAttribute* href = gumbo_get_attribute(&node->v.element.attributes, "href");
+		if (href && strlen(href->value) > 0) return href->value;
 	}
-
-	GumboElement* el = &node->v.element;
-	if (el->tag == GUMBO_TAG_DIV && has_id(el, "main")) {
-		main = node;
-		return;
+	for (GumboNode* node : select_all(root, {{.tag = GUMBO_TAG_LINK}})) {
+		GumboAttribute* rel = gumbo_get_attribute(&node->v.element.attributes, "rel");
+		GumboAttribute* href = gumbo_get_attribute(&node->v.element.attributes, "href");
+		if (rel && href && strcmp(rel->value, "canonical") == 0 && strlen(href->value) > 0) return href->value;
 	}
-	for (unsigned i = 0; i < el->children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(el->children.data[i]);
-		walk_through(child, main);
+	return "";
+}
+
+std::string find_thumbnail(GumboNode* node) {
+	for (GumboNode* img : select_all(node, {{.tag = GUMBO_TAG_IMG}})) {
+		GumboAttribute* src = gumbo_get_attribute(&img->v.element.attributes, "src");
+		if (src && strlen(src->value) > 0) return src->value;
 	}
+	return "";
 }
 
-// find urls from main! 
+struct DetailEntry {
+	std::string id;
+	std::string title;
+	std::string url;
+	std::string thumbnail;
+};
+
+void to_json(nlohmann::json& j, const DetailEntry& e) {
+	j["id"] = string_or_null(e.id);
+	j["title"] = e.title;
+	j["url"] = e.url;
+	j["thumbnail"] = string_or_null(e.thumbnail);
+}
+
+// find entries from main! 
 // assumed structure
 // <div id="main">
 	// <div class="pop..."><div/>
 	// <div class="post-list">
-		// <a href=> </a>
+		// <a href=><img src=>title</a>
 		// <a> </a>
 		//...
 	// <div/>
 // <div/>
 // 
-std::vector<std::string>  find_detail_urls(GumboNode* node) {
-	if (!node) {
-		return {};
-	}
-	GumboElement* post_list = nullptr;
-	// find post-list!
-	for (unsigned i = 0; i < node->v.element.children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(node->v.element.children.data[i]);
-		GumboElement* el = &child->v.element;
-		if (el->tag == GUMBO_TAG_DIV && has_class(el, "post-list")) { 
-			post_list = el;
-		}
-	}
-
+std::vector<DetailEntry> find_detail_entries(GumboNode* main, const std::string& base) {
+	// the last post-list wins
+	GumboNode* post_list = select_last(main, {{.tag = GUMBO_TAG_DIV, .cls = "post-list", .child = true}});
 	if (!post_list) {
 		return {};
 	}
-	std::vector<std::string> v;
-	for (unsigned i = 0; i < post_list->children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(post_list->children.data[i]);
-		GumboElement* el = &child->v.element;
 
-		if (el->tag == GUMBO_TAG_A) {
-			GumboAttribute* attr = gumbo_get_attribute(&el->attributes, "href");
-			if(attr){
-				v.push_back(attr->value);
-			}
+	std::vector<DetailEntry> v;
+	for (GumboNode* a : select_all(post_list, {{.tag = GUMBO_TAG_A, .child = true}})) {
+		GumboAttribute* attr = gumbo_get_attribute(&a->v.element.attributes, "href");
+		if(attr){
+			DetailEntry entry;
+			entry.url = resolve_url(base, attr->value);
+			find_id_from_url(entry.url, entry.id);
+
+			std::string text;
+			inner_text(a, text);
+			entry.title = trim(text);
+
+			std::string src = find_thumbnail(a);
+			if (!src.empty()) entry.thumbnail = resolve_url(base, src);
+
+			v.push_back(std::move(entry));
 		}
 	}
 
 	return v;
 }
 
-extern "C" int find_detail(const char* html, char** result_json) {
-	auto* output = gumbo_parse(html);
+extern "C" int find_detail(const char* html, size_t html_len, char** result_json, size_t* result_len) {
+	auto* output = gumbo_parse_with_options(&kGumboDefaultOptions, html, html_len);
 	if (!output) {
-		return 1;
+		return PARSE_GUMBO_FAILED;
 	}
 	try {
 		GumboNode* node = output->root;
-		GumboNode* main = nullptr;
-		walk_through(node, main);
+		GumboNode* main = select_first(node, {{.tag = GUMBO_TAG_DIV, .id = "main"}});
 
 		if (!main) {
 			gumbo_destroy_output(&kGumboDefaultOptions, output);
-			return 2;
+			return PARSE_MAIN_NOT_FOUND;
 		}
 
-		auto result = find_detail_urls(main);
-		gumbo_destroy_output(&kGumboDefaultOptions, output);
+		std::string base = find_base_url(node);
+		auto result = find_detail_entries(main, base);
 		nlohmann::json j = result;
-		*result_json = strdup(j.dump().c_str());
+		*result_json = copy_result(j.dump(-1, ' ', false, nlohmann::json::error_handler_t::replace), result_len);
+		gumbo_destroy_output(&kGumboDefaultOptions, output);
 
-		return 0;
+		return PARSE_OK;
 
 	} catch (const std::exception& e){
-		std::cout << e.what() << "\n";
+		log_msg(LOG_ERROR, "find_detail", e.what());
 		gumbo_destroy_output(&kGumboDefaultOptions, output);
-		return 3;
+		return PARSE_EXCEPTION;
 	}
 }
-
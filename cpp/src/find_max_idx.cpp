@@ -1,9 +1,10 @@
 #include <cstdio>
+#include <cstring>
 #include <gumbo.h>
 #include <gumbo_search_lib.hpp>
+#include <nlohmann/json.hpp>
 #include <regex>
 #include <string>
-#include <iostream>
 // assumed structure
 // <body>
 	// <div id="container">
@@ -12,7 +13,12 @@
 			// <div> </div>
 			// ...
 			// <div class="wp-pagenavi"> 
-				// <div class="page">1 / 48</div>
+				// <span class="pages">3 / 48</span>
+				// <a class="previouspostslink" href=".../page/2/">«</a>
+				// <a class="page" href=".../page/2/">2</a>
+				// <span class="current">3</span>
+				// <a class="page" href=".../page/4/">4</a>
+				// <a class="nextpostslink" href=".../page/4/">»</a>
 			// </div>
 		// </div>
 	// </div>
@@ -28,96 +34,130 @@
 		// </div>
 	// </div>
 // </body>
-// ---> container ---> main ---> wp-pagenavi ---> page
+// ---> container ---> main ---> wp-pagenavi ---> pages / current / links
 //                       |
 //                       |
-//                       |-----> not found wp-pagenavi
+//                       |-----> not found wp-pagenavi ---> single page
 //                       
 // so first let's find main div!
 
-void find_main(GumboNode* node, GumboNode*& main) {
-	if (!node || node->type != GUMBO_NODE_ELEMENT || main) {
-		return;
-	}
-	GumboElement* el = &node->v.element;
-	if (el->tag == GUMBO_TAG_DIV && has_id(el, "container")) {
-		for (unsigned i = 0; i < el->children.length; i++) {
-			GumboNode* child = static_cast<GumboNode*>(el->children.data[i]);
-			if (child->type == GUMBO_NODE_ELEMENT) {
-				GumboElement* inner = &child->v.element;
-				if (inner->tag == GUMBO_TAG_DIV && has_id(inner , "main")) {
-					main = child;
-					return;
-					}
-				}
-			}
-		}
+struct Pagination {
+	int current = 1;
+	int total = 1;
+	std::string next_url;
+	std::string prev_url;
+	// e.g. "https://example.com/page/{}/"
+	std::string page_url_pattern;
+	bool single_page = true;
+};
 
-	for (unsigned i = 0; i < el->children.length; i++ ) {
-		GumboNode* child = static_cast<GumboNode*>(el->children.data[i]);
-		find_main(child, main);
-	}
+void to_json(nlohmann::json& j, const Pagination& p) {
+	j["current"] = p.current;
+	j["total"] = p.total;
+	j["next_url"] = string_or_null(p.next_url);
+	j["prev_url"] = string_or_null(p.prev_url);
+	j["page_url_pattern"] = string_or_null(p.page_url_pattern);
+	j["single_page"] = p.single_page;
 }
 
-bool find_page(GumboNode* main, int& page) {
-	if (!main) return false;
-	GumboElement* el = &main->v.element;
-	for (unsigned i = 0; i < el->children.length; i++ ) {
-		GumboNode* child = static_cast<GumboNode*>(el->children.data[i]);
-		if (child->type != GUMBO_NODE_ELEMENT) continue;
-		GumboElement* inner = &child->v.element;
-		if (inner->tag == GUMBO_TAG_DIV && has_class(inner, "wp-pagenavi")) {
-			for (unsigned i = 0; i < inner->children.length; i++) {
-				GumboNode* grand_child = (GumboNode*)inner->children.data[i];
-				if (grand_child->type == GUMBO_NODE_ELEMENT && has_class(&grand_child->v.element, "pages")) {
-					std::string text;
-					inner_text(grand_child, text);
-					std::cout << text << "\n";
-
-					std::regex re(R"(\d+ / (\d+))");
-					std::smatch m;
-
-					if (!std::regex_search(text, m, re)) {return false;};
-					std::cout << m[1];
-					int idx = std::stoi(m[1]);
-					page = idx;
-					return true;
-				}
+// ".../page/4/" with page 4 ---> ".../page/{}/"
+std::string to_page_pattern(const std::string& href, int page) {
+	std::string n = std::to_string(page);
+	auto pos = href.rfind(n);
+	if (pos == std::string::npos) return "";
+	return href.substr(0, pos) + "{}" + href.substr(pos + n.size());
+}
+
+bool parse_page_number(const std::string& text, int& page) {
+	std::regex re(R"(^\s*(\d+)\s*$)");
+	std::smatch m;
+	if (!std::regex_search(text, m, re)) return false;
+	page = std::stoi(m[1]);
+	return true;
+}
+
+int find_page(GumboNode* main, Pagination& p) {
+	if (!main) return PARSE_MAIN_NOT_FOUND;
+	GumboNode* pagenavi = select_first(main, {{.tag = GUMBO_TAG_DIV, .cls = "wp-pagenavi", .child = true}});
+	// no wp-pagenavi means everything fits on one page
+	if (!pagenavi) return PARSE_OK;
+
+	p.single_page = false;
+	bool got_total = false;
+	for (GumboNode* grand_child : select_all(pagenavi, {{.child = true}})) {
+		GumboElement* gel = &grand_child->v.element;
+
+		std::string text;
+		inner_text(grand_child, text);
+
+		if (has_class(gel, "pages")) {
+			log_msg(LOG_DEBUG, "find_max_idx", "pages: " + text);
+
+			std::regex re(R"((\d+) / (\d+))");
+			std::smatch m;
+
+			if (!std::regex_search(text, m, re)) {return PARSE_PAGE_COUNT_NOT_FOUND;};
+			log_msg(LOG_DEBUG, "find_max_idx", "max idx: " + m[2].str());
+			p.current = std::stoi(m[1]);
+			p.total = std::stoi(m[2]);
+			got_total = true;
+		} else if (gel->tag == GUMBO_TAG_SPAN && has_class(gel, "current")) {
+			parse_page_number(text, p.current);
+		} else if (gel->tag == GUMBO_TAG_A) {
+			GumboAttribute* href = gumbo_get_attribute(&gel->attributes, "href");
+			if (!href) continue;
+
+			if (has_class(gel, "previouspostslink")) {
+				p.prev_url = href->value;
+			} else if (has_class(gel, "nextpostslink")) {
+				p.next_url = href->value;
+			}
+
+			int n = 0;
+			if (p.page_url_pattern.empty() && parse_page_number(text, n)) {
+				p.page_url_pattern = to_page_pattern(href->value, n);
 			}
 		}
 	}
 
-	return false;
+	if (!got_total) return PARSE_PAGE_COUNT_NOT_FOUND;
+	if (p.page_url_pattern.empty() && !p.next_url.empty()) {
+		p.page_url_pattern = to_page_pattern(p.next_url, p.current + 1);
+	}
+	return PARSE_OK;
 }
 
-extern "C" int find_max_idx(const char* html, int* page) {
-	auto* output = gumbo_parse(html);
-	*page = 0;
+extern "C" int find_max_idx(const char* html, size_t html_len, char** result_json, size_t* result_len) {
+	auto* output = gumbo_parse_with_options(&kGumboDefaultOptions, html, html_len);
 	if (!output) {
-		return 1;
+		return PARSE_GUMBO_FAILED;
 	}
 
 	GumboNode* node = output->root;
-	GumboNode* main = nullptr;
-	int idx = 0;
+	Pagination pagination;
 	try {
-		find_main(node, main);
+		GumboNode* main = select_first(node, {
+			{.tag = GUMBO_TAG_DIV, .id = "container"},
+			{.tag = GUMBO_TAG_DIV, .id = "main", .child = true},
+		});
 		if (!main) {
 			gumbo_destroy_output(&kGumboDefaultOptions, output);
-			return 2;
+			return PARSE_MAIN_NOT_FOUND;
 		};
-		if (!find_page(main, idx)) {
+		int resp = find_page(main, pagination);
+		if (resp != PARSE_OK) {
 			gumbo_destroy_output(&kGumboDefaultOptions, output);
-			return 3;
+			return resp;
 		}
 
-		*page = idx;
+		nlohmann::json j = pagination;
+		*result_json = copy_result(j.dump(-1, ' ', false, nlohmann::json::error_handler_t::replace), result_len);
 		gumbo_destroy_output(&kGumboDefaultOptions, output);
-		return 0;
+		return PARSE_OK;
 	} catch (const std::exception& e) {
-		std::cout << e.what() << std::endl;
+		log_msg(LOG_ERROR, "find_max_idx", e.what());
 		gumbo_destroy_output(&kGumboDefaultOptions, output);
-		return 4;
+		return PARSE_EXCEPTION;
 	}
 
 }
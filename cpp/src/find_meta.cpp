@@ -1,8 +1,9 @@
+#include <atomic>
 #include <cstdio>
+#include <cstdlib>
+#include <regex>
 #include <exception>
-#include <iostream>
 #include <nlohmann/detail/macro_scope.hpp>
-#include <regex>
 #include <cstdint>
 #include <cstring>
 #include <ctime>
@@ -19,73 +20,257 @@
 using json = nlohmann::json;
 NLOHMANN_DEFINE_TYPE_NON_INTRUSIVE(Tag, url, name);
 
-bool find_id_from_url(std::string url, std::string& id) {
-	std::regex re(R"(rj[\d]+)");
-	std::smatch m;
+// schema version of the find_meta output, bump on any breaking change
+// 2: scenario, description, price, file_size, sample_images, og fallbacks, site suffix stripped from title
+// 3: time is the real epoch of the post-time wall clock (was read as utc), time_iso added
+// 4: time_table entries carry start / duration / unparseable, runtime added
+constexpr int META_SCHEMA_VERSION = 4;
 
-	if (!std::regex_search(url, m, re)) {return false;}; 
-	id = m[0];
-	return true;
+static std::atomic<int> meta_utc_offset{540};
+
+extern "C" void set_meta_utc_offset(int minutes) {
+	meta_utc_offset.store(minutes);
 }
-void walk_finding_meta_post_chapter(
-		GumboNode* node,
-		std::string& title,
-		std::string& url, 
-		std::string& img, 
-		int64_t& t,
-		GumboNode* &post_tag,
-		GumboNode* &chapter,
-		bool& got_title,
-		bool& got_url,
-		bool& got_img,
-		bool& got_time
-		) {
 
+// tried in order, the first one that matches the start of the text wins,
+// so a format has to come before its own prefixes
+const char* POST_TIME_FORMATS[] = {
+	"%Y年%m月%d日%H時",
+	"%Y年%m月%d日 %H:%M",
+	"%Y-%m-%dT%H:%M",
+	"%Y-%m-%d %H:%M",
+	"%Y/%m/%d %H:%M",
+	"%Y年%m月%d日",
+	"%Y-%m-%d",
+	"%Y/%m/%d",
+};
+
+// wall time at utc_offset (minutes) ---> epoch seconds
+bool parse_post_time(const std::string& text, int utc_offset, int64_t& t) {
+	for (const char* fmt : POST_TIME_FORMATS) {
+		std::istringstream iss(text);
+		date::local_seconds tp;
+		iss >> date::parse(fmt, tp);
+		if (!iss.fail()) {
+			t = tp.time_since_epoch().count() - int64_t(utc_offset) * 60;
+			return true;
+		}
+	}
+	return false;
+}
+
+// 1709629200, +540 ---> "2024-03-05T18:00:00+09:00"
+std::string to_iso8601(int64_t t, int utc_offset) {
+	date::sys_seconds local{std::chrono::seconds{t + int64_t(utc_offset) * 60}};
+	int abs_offset = std::abs(utc_offset);
+	char suffix[8];
+	snprintf(suffix, sizeof(suffix), "%c%02d:%02d", utc_offset < 0 ? '-' : '+', abs_offset / 60, abs_offset % 60);
+	return date::format("%FT%T", local) + suffix;
+}
+
+// assumed structure
+// <head>
+	// <title>work title | site name</title>
+	// <link rel="canonical" href="https://example.com/rj01234567/">
+	// <meta name="description" content="...">
+	// <meta property="og:image" content="..."> (og:title, og:url, og:description, og:site_name)
+// </head>
+// <body>
+	// ...
+	// <video poster="..."></video>
+	// <div id="post-time">2024年03月05日18時</div> (see POST_TIME_FORMATS)
+	// <div id="post-price">1,320円</div>
+	// <div id="post-size">123.45MB</div>
+	// <div id="post-runtime">1時間02分30秒</div> (or 1:02:30)
+	// <div id="post-tag"> <span>声優</span><a/>... </div>
+	// <div id="sample"> <img src=""/>... </div>
+	// <div id="chapter"> <a>title<span>00:00</span></a>... </div>
+// </body>
+struct MetaWalk {
+	std::string title;
+	std::string url;
+	std::string img;
+	std::string time;
+	std::string description;
+	std::string price;
+	std::string file_size;
+	std::string runtime;
+	GumboNode* post_tag = nullptr;
+	GumboNode* chapter = nullptr;
+	GumboNode* sample = nullptr;
+
+	std::string og_title;
+	std::string og_url;
+	std::string og_image;
+	std::string og_description;
+	std::string og_site_name;
+
+	bool got_title = false;
+	bool got_url = false;
+	bool got_img = false;
+	bool got_time = false;
+	bool got_description = false;
+	bool got_price = false;
+	bool got_file_size = false;
+	bool got_runtime = false;
+
+	bool is_done() const {
+		return got_title && got_img && got_url && got_time && got_description && got_price && got_file_size && got_runtime
+			&& post_tag && chapter && sample
+			&& !og_title.empty() && !og_url.empty() && !og_image.empty() && !og_description.empty() && !og_site_name.empty();
+	}
+};
+
+void walk_finding_meta_post_chapter(GumboNode* node, MetaWalk& w) {
 	if (!node || node->type != GUMBO_NODE_ELEMENT) return;
 	auto *el = &node->v.element;
 
-	if (!got_url && el->tag == GUMBO_TAG_LINK) {
+	if (!w.got_url && el->tag == GUMBO_TAG_LINK) {
 		GumboAttribute* rel = gumbo_get_attribute(&el->attributes, "rel");
 		if (rel && strcmp(rel->value, "canonical") == 0) {
 			auto* href = gumbo_get_attribute(&el->attributes, "href");
-			if (href) {url = href->value; got_url = true;}
+			if (href) {w.url = href->value; w.got_url = true;}
 		}
 	}
 
-	if (!got_title && el->tag == GUMBO_TAG_TITLE) {
-		inner_text(node, title);
-		got_title = true;
+	if (el->tag == GUMBO_TAG_META) {
+		auto* property = gumbo_get_attribute(&el->attributes, "property");
+		auto* name = gumbo_get_attribute(&el->attributes, "name");
+		auto* content = gumbo_get_attribute(&el->attributes, "content");
+		if (content && property) {
+			std::string p = property->value;
+			if (p == "og:title" && w.og_title.empty()) w.og_title = content->value;
+			if (p == "og:url" && w.og_url.empty()) w.og_url = content->value;
+			if (p == "og:image" && w.og_image.empty()) w.og_image = content->value;
+			if (p == "og:description" && w.og_description.empty()) w.og_description = content->value;
+			if (p == "og:site_name" && w.og_site_name.empty()) w.og_site_name = content->value;
+		}
+		if (!w.got_description && content && name && strcmp(name->value, "description") == 0) {
+			w.description = content->value;
+			w.got_description = true;
+		}
 	}
 
-	if (!got_img && el->tag == GUMBO_TAG_VIDEO) {
+	if (!w.got_title && el->tag == GUMBO_TAG_TITLE) {
+		inner_text(node, w.title);
+		w.got_title = true;
+	}
+
+	if (!w.got_img && el->tag == GUMBO_TAG_VIDEO) {
 		auto* p = gumbo_get_attribute(&el->attributes, "poster");
-		if(p) {img = p->value; got_img = true;}
+		if(p) {w.img = p->value; w.got_img = true;}
 	}
  
-	if (!got_time && el->tag == GUMBO_TAG_DIV && has_id(el, "post-time")) {
-		std::string out;
-		inner_text(node, out);
-		std::stringstream iss(out);
-		date::sys_seconds tp;
-		iss >> date::parse("%Y年%m月%d日%H時", tp);
-		t = tp.time_since_epoch().count();
-		got_time = true;
+	if (!w.got_time && matches(node, {.tag = GUMBO_TAG_DIV, .id = "post-time"})) {
+		inner_text(node, w.time);
+		w.time = trim(w.time);
+		w.got_time = true;
 	}
 
-	if (el->tag == GUMBO_TAG_DIV && has_id(el, "post-tag")) post_tag = node;
-	if (el->tag == GUMBO_TAG_DIV && has_id(el, "chapter")) chapter = node;
+	if (!w.got_price && matches(node, {.tag = GUMBO_TAG_DIV, .id = "post-price"})) {
+		inner_text(node, w.price);
+		w.got_price = true;
+	}
 
-	if (got_title && got_img && got_url && got_time && post_tag && chapter) return;
+	if (!w.got_file_size && matches(node, {.tag = GUMBO_TAG_DIV, .id = "post-size"})) {
+		inner_text(node, w.file_size);
+		w.file_size = trim(w.file_size);
+		w.got_file_size = true;
+	}
+
+	if (!w.got_runtime && matches(node, {.tag = GUMBO_TAG_DIV, .id = "post-runtime"})) {
+		inner_text(node, w.runtime);
+		w.got_runtime = true;
+	}
+
+	if (matches(node, {.tag = GUMBO_TAG_DIV, .id = "post-tag"})) w.post_tag = node;
+	if (matches(node, {.tag = GUMBO_TAG_DIV, .id = "chapter"})) w.chapter = node;
+	if (matches(node, {.tag = GUMBO_TAG_DIV, .id = "sample"})) w.sample = node;
+
+	if (w.is_done()) return;
 
 	for (unsigned i = 0; i < el->children.length; ++i) {
-		walk_finding_meta_post_chapter((GumboNode*)el->children.data[i], title, url, img, t, post_tag, chapter, got_title, got_url, got_img, got_time);
+		walk_finding_meta_post_chapter((GumboNode*)el->children.data[i], w);
 	}
 }
 
+// "1,320円" ---> 1320, no digits (or too many for int64) ---> -1
+int64_t parse_price(const std::string& text) {
+	std::string digits;
+	for (char c : text) {
+		if (c >= '0' && c <= '9') digits.push_back(c);
+	}
+	if (digits.empty() || digits.size() > 18) return -1;
+	return std::stoll(digits);
+}
+
+// "work title | site name" ---> "work title"
+// with og:site_name the suffix has to be exactly the site name, otherwise only " | " is treated as a separator
+std::string strip_site_suffix(const std::string& raw, const std::string& site_name) {
+	std::string title = trim(raw);
+	if (!site_name.empty() && title.size() > site_name.size()
+			&& title.compare(title.size() - site_name.size(), site_name.size(), site_name) == 0) {
+		std::string rest = trim(title.substr(0, title.size() - site_name.size()));
+		for (const char* sep : {"|", "-", "–", "—", "｜"}) {
+			size_t len = strlen(sep);
+			if (rest.size() >= len && rest.compare(rest.size() - len, len, sep) == 0) {
+				rest = trim(rest.substr(0, rest.size() - len));
+				break;
+			}
+		}
+		if (!rest.empty()) return rest;
+	}
+
+	auto pos = title.rfind(" | ");
+	if (pos != std::string::npos && pos > 0) {
+		return trim(title.substr(0, pos));
+	}
+	return title;
+}
+
+std::vector<std::string> find_sample_images(GumboNode* node, const std::string& base) {
+	std::vector<std::string> v;
+	if (!node) return v;
+
+	for (GumboNode* img : select_all(node, {{.tag = GUMBO_TAG_IMG}})) {
+		auto* src = gumbo_get_attribute(&img->v.element.attributes, "src");
+		if (src && strlen(src->value) > 0) v.push_back(resolve_url(base, src->value));
+	}
+	return v;
+}
+
+// "03:12" / "1:02:30" / "1時間2分30秒" / "62分" ---> seconds, -1 when unparseable
+int64_t parse_duration(const std::string& raw) {
+	std::string text = trim(raw);
+	std::smatch m;
+
+	std::regex clock(R"(^(?:([0-9]{1,9}):)?([0-9]{1,9}):([0-9]{1,9})$)");
+	if (std::regex_search(text, m, clock)) {
+		int64_t h = m[1].matched ? std::stoll(m[1]) : 0;
+		int64_t mi = std::stoll(m[2]);
+		int64_t se = std::stoll(m[3]);
+		if (se >= 60 || (m[1].matched && mi >= 60)) return -1;
+		return h * 3600 + mi * 60 + se;
+	}
+
+	std::regex units(R"(^(?:([0-9]{1,9})時間)?(?:([0-9]{1,9})分)?(?:([0-9]{1,9})秒)?$)");
+	if (!text.empty() && std::regex_search(text, m, units)) {
+		int64_t h = m[1].matched ? std::stoll(m[1]) : 0;
+		int64_t mi = m[2].matched ? std::stoll(m[2]) : 0;
+		int64_t se = m[3].matched ? std::stoll(m[3]) : 0;
+		return h * 3600 + mi * 60 + se;
+	}
+
+	return -1;
+}
+
 struct TimeTable {
 	int index;
 	std::string title;
 	std::string time;
+	// seconds, -1 when unknown
+	int64_t start = -1;
+	int64_t duration = -1;
 	
 	explicit TimeTable(int _index, std::string&& _title, std::string&& _time) noexcept : index(_index), title(std::move(_title)), time(std::move(_time)) {}
 
@@ -93,16 +278,40 @@ struct TimeTable {
 	TimeTable(const TimeTable&) = delete;
 	TimeTable& operator=(const TimeTable&) = delete;
 
-	TimeTable(TimeTable&& t) noexcept : index(t.index), title(std::move(t.title)), time(std::move(t.time)) {};
+	TimeTable(TimeTable&& t) noexcept : index(t.index), title(std::move(t.title)), time(std::move(t.time)), start(t.start), duration(t.duration) {};
 	TimeTable& operator=(TimeTable&& t) noexcept {
 		index = t.index;
 		title = std::move(t.title);
 		time = std::move(t.time);
+		start = t.start;
+		duration = t.duration;
 
 		return *this;
 	};
 };
-NLOHMANN_DEFINE_TYPE_NON_INTRUSIVE(TimeTable, index, title, time);
+
+static json seconds_or_null(int64_t s) {
+	return s < 0 ? json(nullptr) : json(s);
+}
+
+void to_json(json& j, const TimeTable& t) {
+	j["index"] = t.index;
+	j["title"] = t.title;
+	j["time"] = t.time;
+	j["start"] = seconds_or_null(t.start);
+	j["duration"] = seconds_or_null(t.duration);
+	j["unparseable"] = t.start < 0;
+}
+
+// a chapter lasts until the next one starts, the last one until the end of the work (runtime)
+// missing or out of order neighbours leave the duration unknown
+void fill_durations(std::vector<TimeTable>& v, int64_t runtime) {
+	for (size_t i = 0; i < v.size(); i++) {
+		if (v[i].start < 0) continue;
+		int64_t end = i + 1 < v.size() ? v[i + 1].start : runtime;
+		if (end >= v[i].start) v[i].duration = end - v[i].start;
+	}
+}
 
 void find_tags_from_post_div(GumboNode* node,  
 		std::vector<Tag>& cv,
@@ -113,10 +322,7 @@ void find_tags_from_post_div(GumboNode* node,
 		std::vector<Tag>& series
 		) {
 	std::string title;
-	for (unsigned i = 0; i < node->v.element.children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(node->v.element.children.data[i]);
-
-		if (child->type != GUMBO_NODE_ELEMENT) continue;
+	for (GumboNode* child : select_all(node, {{.child = true}})) {
 		GumboElement* el = &child->v.element;
 		if (el->tag == GUMBO_TAG_SPAN) {
 			std::string out;
@@ -157,10 +363,7 @@ std::vector<TimeTable> find_table_from_chapter_div(GumboNode* node) {
 	std::vector<TimeTable> v{};
 	int index  = 0;
 
-	for (unsigned i = 0; i < node->v.element.children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(node->v.element.children.data[i]);
-		if (child->type != GUMBO_NODE_ELEMENT || child->v.element.tag != GUMBO_TAG_A) continue;
-
+	for (GumboNode* child : select_all(node, {{.tag = GUMBO_TAG_A, .child = true}})) {
 		GumboElement* link = &child->v.element;
 		std::string title, time;
 		for (unsigned i = 0; i < link->children.length; i++) {
@@ -178,45 +381,46 @@ std::vector<TimeTable> find_table_from_chapter_div(GumboNode* node) {
 
 		if (!title.empty() && !time.empty()) {
 			TimeTable result(index++, std::move(title), std::move(time));
+			result.start = parse_duration(result.time);
 			v.push_back(std::move(result));
 		}
 	}
 	return v;
 }
 
-extern "C" int find_meta(const char* html, char** result_json) {
-	auto* output = gumbo_parse(html);
+extern "C" int find_meta(const char* html, size_t html_len, char** result_json, size_t* result_len) {
+	auto* output = gumbo_parse_with_options(&kGumboDefaultOptions, html, html_len);
 	if (!output) {
-		return 1;
+		return PARSE_GUMBO_FAILED;
 	}
 	GumboNode* node = output->root;
-	std::string title;
-	std::string url; 
-	std::string img; 
-	int64_t t = 0;
-	GumboNode* post_tag = nullptr;
-	GumboNode* chapter = nullptr;
-	bool got_title = false;
-	bool got_url = false;
-	bool got_img = false;
-	bool got_time = false;
+	MetaWalk w;
 	try {
-		walk_finding_meta_post_chapter(
-				node,
-				title,
-				url, 
-				img, 
-				t,
-				post_tag,
-				chapter,
-				got_title,
-				got_url,
-				got_img,
-				got_time
-				); 
-		if (url.empty() || title.empty() || t == 0) {
+		walk_finding_meta_post_chapter(node, w); 
+
+		// og fallbacks
+		std::string url = w.url.empty() ? w.og_url : w.url;
+		std::string title = w.title.empty() ? trim(w.og_title) : strip_site_suffix(w.title, w.og_site_name);
+		std::string img = w.img.empty() ? w.og_image : w.img;
+		std::string description = trim(w.description.empty() ? w.og_description : w.description);
+
+		int utc_offset = meta_utc_offset.load();
+		int64_t t = 0;
+
+		int resp = PARSE_OK;
+		if (url.empty()) {
+			resp = PARSE_CANONICAL_URL_NOT_FOUND;
+		} else if (title.empty()) {
+			resp = PARSE_TITLE_NOT_FOUND;
+		} else if (w.time.empty()) {
+			resp = PARSE_POST_TIME_NOT_FOUND;
+		} else if (!parse_post_time(w.time, utc_offset, t)) {
+			log_msg(LOG_WARN, "find_meta", "unparseable post-time: " + w.time);
+			resp = PARSE_POST_TIME_UNPARSEABLE;
+		}
+		if (resp != PARSE_OK) {
 			gumbo_destroy_output(&kGumboDefaultOptions, output);
-			return 2;
+			return resp;
 		}
 
 		std::vector<Tag> cv;
@@ -225,41 +429,53 @@ extern "C" int find_meta(const char* html, char** result_json) {
 		std::vector<Tag> illust;
 		std::vector<Tag> genre;
 		std::vector<Tag> series;
-		if (post_tag) {
-			find_tags_from_post_div(post_tag, cv, circle, scenario, illust, genre, series);
+		if (w.post_tag) {
+			find_tags_from_post_div(w.post_tag, cv, circle, scenario, illust, genre, series);
 		}
+		int64_t runtime = w.got_runtime ? parse_duration(w.runtime) : -1;
 		std::vector<TimeTable> time_tables;
-		if (chapter) {
-			time_tables = find_table_from_chapter_div(chapter);
+		if (w.chapter) {
+			time_tables = find_table_from_chapter_div(w.chapter);
 		}
+		fill_durations(time_tables, runtime);
+		std::vector<std::string> sample_images = find_sample_images(w.sample, url);
 
 		json j;
 		std::string id;
 		if (!find_id_from_url(url, id)) {
 			gumbo_destroy_output(&kGumboDefaultOptions, output);
-			return 3;
+			return PARSE_WORK_ID_NOT_FOUND;
 		}
+		j["schema_version"] = META_SCHEMA_VERSION;
 		j["id"] = id;
 		j["title"] = title;
 		j["url"] = url;
-		j["img_src"] = img.empty() ? nullptr : img;
+		j["img_src"] = string_or_null(img);
 		j["time"] = t;
+		j["time_iso"] = to_iso8601(t, utc_offset);
+		j["description"] = string_or_null(description);
+		int64_t price = parse_price(w.price);
+		j["price"] = price < 0 ? json(nullptr) : json(price);
+		j["file_size"] = string_or_null(w.file_size);
+		j["sample_images"] = sample_images;
 
 		j["cv"] = cv;
 		j["genre"] = genre;
 		j["illust"] = illust;
 		j["circle"] = circle;
+		j["scenario"] = scenario;
 		j["series"] = series;
 
 		j["time_table"] = time_tables;
-		std::string dumped = j.dump();
-		*result_json = strdup(dumped.c_str());
+		j["runtime"] = seconds_or_null(runtime);
+		std::string dumped = j.dump(-1, ' ', false, json::error_handler_t::replace);
+		*result_json = copy_result(dumped, result_len);
 
 		gumbo_destroy_output(&kGumboDefaultOptions, output);
 	} catch (const std::exception& e) {
-		std::cout << e.what() << std::endl;
-		return 4;
+		log_msg(LOG_ERROR, "find_meta", e.what());
+		gumbo_destroy_output(&kGumboDefaultOptions, output);
+		return PARSE_EXCEPTION;
 	}
-	return 0;
-} 
-
+	return PARSE_OK;
+}
@@ -8,6 +8,9 @@
 #include <gumbo_search_lib.hpp>
 #include <utility>
 #include <nlohmann/json.hpp>
+#include <atomic>
+#include <iostream>
+#include <regex>
 
 Tag::Tag(Tag&& t) noexcept : name(std::move(t.name)), url(std::move(t.url)) {}
 Tag& Tag::operator=(Tag&& t) noexcept {
@@ -17,12 +20,41 @@ Tag& Tag::operator=(Tag&& t) noexcept {
 	return *this;
 }
 
+static std::atomic<log_callback_t> log_callback{nullptr};
+
+extern "C" void set_log_callback(log_callback_t callback) {
+	log_callback.store(callback);
+}
+
+void log_msg(int level, const char* parser, const std::string& msg) {
+	log_callback_t callback = log_callback.load();
+	if (callback) {
+		callback(level, parser, strlen(parser), msg.data(), msg.size());
+		return;
+	}
+	// nothing registered yet, never write to stdout
+	std::cerr << parser << ": " << msg << "\n";
+}
+
 extern "C" void free_char(char* s) {
 	if (s) {
 		free(s);
 	}
 }
 
+char* copy_result(const std::string& s, size_t* len) {
+	char* buf = static_cast<char*>(malloc(s.size() + 1));
+	if (!buf) {
+		*len = 0;
+		return nullptr;
+	}
+	memcpy(buf, s.data(), s.size());
+	buf[s.size()] = '\0';
+	*len = s.size();
+
+	return buf;
+}
+
 bool has_id(GumboElement* el,  const char* id) {
 	GumboAttribute* attr = gumbo_get_attribute(&el->attributes, "id");
 	return attr && strcmp(attr->value, id) == 0;
@@ -30,7 +62,72 @@ bool has_id(GumboElement* el,  const char* id) {
 
 bool has_class(GumboElement* el,  const char* _class) {
 	GumboAttribute* attr = gumbo_get_attribute(&el->attributes, "class");
-	return attr && strcmp(attr->value, _class) == 0;
+	if (!attr) return false;
+
+	const char* ws = " \t\n\r\f";
+	size_t len = strlen(_class);
+	const char* p = attr->value;
+	while (*p) {
+		p += strspn(p, ws);
+		size_t token = strcspn(p, ws);
+		if (token == len && token > 0 && strncmp(p, _class, len) == 0) return true;
+		p += token;
+	}
+	return false;
+}
+
+bool matches(GumboNode* node, const Selector& sel) {
+	if (!node || node->type != GUMBO_NODE_ELEMENT) return false;
+	GumboElement* el = &node->v.element;
+
+	if (sel.tag != GUMBO_TAG_UNKNOWN && el->tag != sel.tag) return false;
+	if (sel.id && !has_id(el, sel.id)) return false;
+	if (sel.cls && !has_class(el, sel.cls)) return false;
+	return true;
+}
+
+// candidates for one step below node, preorder
+static void collect_step(GumboNode* node, const Selector& sel, std::vector<GumboNode*>& out) {
+	if (!node || node->type != GUMBO_NODE_ELEMENT) return;
+	GumboVector* children = &node->v.element.children;
+
+	for (unsigned i = 0; i < children->length; i++) {
+		GumboNode* child = static_cast<GumboNode*>(children->data[i]);
+		if (matches(child, sel)) out.push_back(child);
+		if (!sel.child) collect_step(child, sel, out);
+	}
+}
+
+std::vector<GumboNode*> select_all(GumboNode* root, std::initializer_list<Selector> chain) {
+	std::vector<GumboNode*> current{root};
+	for (const Selector& sel : chain) {
+		std::vector<GumboNode*> next;
+		for (GumboNode* node : current) {
+			collect_step(node, sel, next);
+		}
+
+		// nested matches of a descendant step reach the same nodes twice
+		std::vector<GumboNode*> unique;
+		for (GumboNode* node : next) {
+			bool seen = false;
+			for (GumboNode* u : unique) {
+				if (u == node) { seen = true; break; }
+			}
+			if (!seen) unique.push_back(node);
+		}
+		current = std::move(unique);
+	}
+	return current;
+}
+
+GumboNode* select_first(GumboNode* root, std::initializer_list<Selector> chain) {
+	auto v = select_all(root, chain);
+	return v.empty() ? nullptr : v.front();
+}
+
+GumboNode* select_last(GumboNode* root, std::initializer_list<Selector> chain) {
+	auto v = select_all(root, chain);
+	return v.empty() ? nullptr : v.back();
 }
 
 void inner_text(GumboNode* node,  std::string& out) {
@@ -55,3 +152,56 @@ void inner_text(GumboNode* node,  std::string& out) {
 			 return;
 	}
 }
+
+nlohmann::json string_or_null(const std::string& s) {
+	return s.empty() ? nlohmann::json(nullptr) : nlohmann::json(s);
+}
+
+std::string trim(const std::string& s) {
+	const char* ws = " \t\n\r\f";
+	auto begin = s.find_first_not_of(ws);
+	if (begin == std::string::npos) return "";
+	auto end = s.find_last_not_of(ws);
+	return s.substr(begin, end - begin + 1);
+}
+
+bool find_id_from_url(const std::string& url, std::string& id) {
+	std::regex re(R"(rj[\d]+)");
+	std::smatch m;
+
+	if (!std::regex_search(url, m, re)) {return false;}; 
+	id = m[0];
+	return true;
+}
+
+// base                          href           result
+// https://example.com/a/b/      c/             https://example.com/a/b/c/
+// https://example.com/a/b/      /c/            https://example.com/c/
+// https://example.com/a/b/      //cdn.com/c    https://cdn.com/c
+// https://example.com/a/b/      ?page=2        https://example.com/a/b/?page=2
+std::string resolve_url(const std::string& base, const std::string& href) {
+	auto scheme_end = href.find("://");
+	if (base.empty() || (scheme_end != std::string::npos && scheme_end < href.find('/'))) {
+		return href;
+	}
+
+	auto base_scheme_end = base.find("://");
+	if (base_scheme_end == std::string::npos) return href;
+
+	if (href.rfind("//", 0) == 0) {
+		return base.substr(0, base_scheme_end + 1) + href;
+	}
+
+	auto path_begin = base.find('/', base_scheme_end + 3);
+	std::string origin = path_begin == std::string::npos ? base : base.substr(0, path_begin);
+	std::string without_fragment = base.substr(0, base.find('#'));
+	std::string without_query = without_fragment.substr(0, without_fragment.find('?'));
+
+	if (href.empty()) return without_fragment;
+	if (href[0] == '/') return origin + href;
+	if (href[0] == '#') return without_fragment + href;
+	if (href[0] == '?') return without_query + href;
+
+	if (without_query.size() <= origin.size()) return origin + "/" + href;
+	return without_query.substr(0, without_query.rfind('/') + 1) + href;
+}
@@ -2,98 +2,72 @@
 #include <vector>
 #include <gumbo_search_lib.hpp>
 #include <nlohmann/json.hpp>
-#include <iostream>
-// find main!
-
 NLOHMANN_DEFINE_TYPE_NON_INTRUSIVE(Tag, url, name);
-void find_main_div(GumboNode* node, GumboNode*& main) {
-	if(node->type != GUMBO_NODE_ELEMENT || main) {
-		return;
-	}
-
-	GumboElement* el = &node->v.element;
-	if (el->tag == GUMBO_TAG_DIV && has_id(el, "main")) {
-		main = node;
-		return;
-	}
-
-	for (unsigned i = 0; i < el->children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(el->children.data[i]);
-		find_main_div(child, main);
-	}
-}
 
 // find tag-list from main and then, extract tags
+// assumed structure
+// <div id="main">
+	// <div class="tag-list">
+		// <a href=>name</a>
+		//...
+	// <div/>
+// <div/>
 std::vector<Tag> extract_tags(GumboNode* main, int& resp) {
-	GumboElement* el = &main->v.element;
-	GumboNode* tag_list_node = nullptr;
-
-	for (unsigned i = 0; i < el->children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(el->children.data[i]);
-		GumboElement* child_el = &child->v.element;
-
-		if (child_el->tag == GUMBO_TAG_DIV && has_class(child_el, "tag-list")) {
-			tag_list_node = child;
-		}
-	}
+	// the last tag-list wins
+	GumboNode* tag_list_node = select_last(main, {{.tag = GUMBO_TAG_DIV, .cls = "tag-list", .child = true}});
 
 	if (!tag_list_node) {
-		resp = 100;
+		resp = PARSE_TAG_LIST_NOT_FOUND;
 		return {};
 	};
-	GumboElement* tag_list_el = &tag_list_node->v.element;
 	std::vector<Tag> result;
-	for (unsigned i = 0; i < tag_list_el->children.length; i++) {
-		GumboNode* child = static_cast<GumboNode*>(tag_list_el->children.data[i]);
-		if (child->type != GUMBO_NODE_ELEMENT ) continue;
+	for (GumboNode* child : select_all(tag_list_node, {{.tag = GUMBO_TAG_A, .child = true}})) {
 		GumboElement* child_el = &child->v.element;
 
-		if (child_el->tag == GUMBO_TAG_A) {
-			std::string text;
-			for (unsigned j = 0; j < child_el->children.length; j++) {
-				GumboNode* grand_child = (GumboNode*)child_el->children.data[j];
-				if (grand_child->type != GUMBO_NODE_TEXT) {
-					continue;
-				}
-				text = grand_child->v.text.text;
+		std::string text;
+		for (unsigned j = 0; j < child_el->children.length; j++) {
+			GumboNode* grand_child = (GumboNode*)child_el->children.data[j];
+			if (grand_child->type != GUMBO_NODE_TEXT) {
+				continue;
 			}
-			GumboAttribute* attr = gumbo_get_attribute(&child_el->attributes, "href");
-			if (!attr) continue;
-			std::string href = attr->value;
-
-			result.push_back(Tag(std::move(text), std::move(href)));
+			text = grand_child->v.text.text;
 		}
+		GumboAttribute* attr = gumbo_get_attribute(&child_el->attributes, "href");
+		if (!attr) continue;
+		std::string href = attr->value;
+
+		result.push_back(Tag(std::move(text), std::move(href)));
 	}
 
 	return result;
 }
 
-extern "C" int update_tag(const char* html, char** result_json) {
-	auto* output = gumbo_parse(html);
+extern "C" int update_tag(const char* html, size_t html_len, char** result_json, size_t* result_len) {
+	auto* output = gumbo_parse_with_options(&kGumboDefaultOptions, html, html_len);
 	if (!output) {
-		return 1;
+		return PARSE_GUMBO_FAILED;
 	}
 
 	try {
 		GumboNode* node = output->root;
-		GumboNode* main = nullptr;
-		find_main_div(node, main);
+		GumboNode* main = select_first(node, {{.tag = GUMBO_TAG_DIV, .id = "main"}});
 
 		if (!main) {
 			gumbo_destroy_output(&kGumboDefaultOptions, output);
-			return 2;
+			return PARSE_MAIN_NOT_FOUND;
 		}
 
-		int resp = 0;
+		int resp = PARSE_OK;
 		auto result = extract_tags(main, resp);
-		gumbo_destroy_output(&kGumboDefaultOptions, output);
 		nlohmann::json j = result;
-		std::string dumped = j.dump();
-		*result_json = strdup(dumped.c_str());
+		std::string dumped = j.dump(-1, ' ', false, nlohmann::json::error_handler_t::replace);
+		*result_json = copy_result(dumped, result_len);
+		gumbo_destroy_output(&kGumboDefaultOptions, output);
 
 		return resp;
 	} catch (const std::exception& e) {
-		std::cout << e.what() << std::endl;
-		return 4;
+		log_msg(LOG_ERROR, "update_tag", e.what());
+		gumbo_destroy_output(&kGumboDefaultOptions, output);
+		return PARSE_EXCEPTION;
 	}
 }
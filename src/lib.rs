@@ -0,0 +1,6 @@
+pub mod parser;
+pub mod redis_communication;
+pub mod redis_lib;
+pub mod scraper;
+pub mod serv_engine;
+pub mod thread_handler;
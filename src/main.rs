@@ -1,24 +1,45 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use bb8::Pool;
 use bb8_redis::RedisConnectionManager;
+use encoding_rs::Encoding;
 use redis::AsyncConnectionConfig;
-use tokio::sync::Semaphore;
+use tokio::{sync::Semaphore, task::JoinSet};
+use tokio_util::sync::CancellationToken;
 
-use crate::{
-    parser::{ffi_parser_factory, find_detail, find_meta, max_idx_finder, update_tag},
+#[cfg(feature = "ffi-parser")]
+use rust::parser::init_ffi_logging;
+#[cfg(feature = "rust-parser")]
+use rust::parser::rust_parser;
+#[cfg(not(feature = "rust-parser"))]
+use rust::parser::{ffi_parser_factory, find_detail, find_meta, max_idx_finder, update_tag};
+use rust::{
+    parser::{
+        ParserErr,
+        extractor::{Extractor, load_extractor_config},
+        init_meta_timezone, parse_utc_offset,
+        worker::{PARSER_WORKER_ARG, WorkerPool, run_parser_worker},
+    },
     redis_communication::BasicRedisReq,
     redis_lib::{ClientAcquireConfig, PoolAcquireConfig, ReqFetchContract},
-    scraper::generate_scraper,
-    serv_engine::{ProcessReqContract, create_path},
+    scraper::{
+        ArchiveSink, ArchiveStore, ClusterRateLimiter, ConditionalGet, HostRateLimiter,
+        HttpClientConfig, ParsePool, ProxyPool, ProxySelection, RateLimiter, RetryPolicy,
+        RobotsPolicy, generate_scraper, invoke_archive_pruner, invoke_parse_pool_reporter,
+        parse_encoding, parse_header_list, unix_millis,
+    },
+    serv_engine::{
+        self, ProcessReqContract,
+        drift::DriftConfig,
+        replay::{REPLAY_ARG, ReplayArgs, ReplayContract, ReplayParser, replay_path},
+    },
+    thread_handler::ThreadHandler,
 };
 
-mod parser;
-mod redis_communication;
-mod redis_lib;
-mod scraper;
-mod serv_engine;
-mod thread_handler;
+type BuiltinParser = Box<dyn Fn(&str) -> Result<String, ParserErr> + Send + Sync>;
 
 macro_rules! get_env {
     ($keyword:expr) => {
@@ -38,12 +59,227 @@ fn temp_backoff_next(current: Duration) -> Duration {
     current.mul_f64(1.5)
 }
 
+// optional, the parsers read div#post-time as JST by default
+fn init_meta_timezone_from_env() {
+    if let Ok(tz) = std::env::var("META_TIME_ZONE") {
+        let minutes =
+            parse_utc_offset(&tz).unwrap_or_else(|| panic!("failed to parse META_TIME_ZONE: {tz}"));
+        init_meta_timezone(minutes);
+    }
+}
+
+// <KEY>_<PATH> overrides <KEY>, e.g. HTTP_TOTAL_TIMEOUT_MS_META over HTTP_TOTAL_TIMEOUT_MS
+fn path_env(key: &str, path: &str) -> Option<String> {
+    std::env::var(format!("{key}_{}", path.to_uppercase()))
+        .or_else(|_| std::env::var(key))
+        .ok()
+}
+
+fn http_client_config(path: &str) -> HttpClientConfig {
+    let parse = |key: &str| {
+        path_env(key, path).map(|value| {
+            value
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("failed to parse {key}"))
+        })
+    };
+    let flag = |key: &str| {
+        path_env(key, path).map(|value| {
+            value
+                .parse::<bool>()
+                .unwrap_or_else(|_| panic!("failed to parse {key}"))
+        })
+    };
+    let default = HttpClientConfig::default();
+
+    HttpClientConfig {
+        connect_timeout: parse("HTTP_CONNECT_TIMEOUT_MS").map(Duration::from_millis),
+        read_timeout: parse("HTTP_READ_TIMEOUT_MS").map(Duration::from_millis),
+        total_timeout: parse("HTTP_TOTAL_TIMEOUT_MS").map(Duration::from_millis),
+        user_agent: path_env("HTTP_USER_AGENT", path),
+        accept_language: path_env("HTTP_ACCEPT_LANGUAGE", path),
+        default_headers: path_env("HTTP_HEADERS", path)
+            .map(|headers| {
+                parse_header_list(&headers)
+                    .unwrap_or_else(|| panic!("failed to parse HTTP_HEADERS: {headers}"))
+            })
+            .unwrap_or_default(),
+        gzip: flag("HTTP_GZIP").unwrap_or(default.gzip),
+        brotli: flag("HTTP_BROTLI").unwrap_or(default.brotli),
+        max_redirects: parse("HTTP_MAX_REDIRECTS")
+            .map(|max| max as usize)
+            .unwrap_or(default.max_redirects),
+    }
+}
+
+// CHARSET_FALLBACK, e.g. shift_jis, for pages without bom, charset header or <meta charset>
+fn fallback_encoding(path: &str) -> &'static Encoding {
+    let label = path_env("CHARSET_FALLBACK", path).expect("failed to load: CHARSET_FALLBACK");
+    parse_encoding(&label).unwrap_or_else(|| panic!("unknown CHARSET_FALLBACK: {label}"))
+}
+
+// NET_REQUEST_RETRY is the attempt count of every path, see path_env for overrides
+fn retry_policy(path: &str) -> RetryPolicy {
+    let parse = |key: &str| {
+        path_env(key, path).map(|value| {
+            value
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("failed to parse {key}"))
+        })
+    };
+    let default = RetryPolicy::default();
+
+    RetryPolicy {
+        max_attempts: path_env("NET_REQUEST_RETRY", path)
+            .expect("failed to load: NET_REQUEST_RETRY")
+            .parse::<i32>()
+            .expect("failed to parse NET_REQUEST_RETRY"),
+        init_backoff: parse("RETRY_INIT_BACKOFF_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(default.init_backoff),
+        max_backoff: parse("RETRY_MAX_BACKOFF_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(default.max_backoff),
+        jitter: path_env("RETRY_JITTER", path)
+            .map(|jitter| jitter.parse::<f64>().expect("failed to parse RETRY_JITTER"))
+            .unwrap_or(default.jitter),
+        max_retry_after: parse("RETRY_MAX_RETRY_AFTER_MS")
+            .map(Duration::from_millis)
+            .unwrap_or(default.max_retry_after),
+    }
+}
+
+fn drift_config(path: &str) -> DriftConfig {
+    let config = DriftConfig {
+        window: get_env_with_parsing!("DRIFT_WINDOW", usize),
+        min_samples: get_env_with_parsing!("DRIFT_MIN_SAMPLES", usize),
+        empty_rate_threshold: get_env_with_parsing!("DRIFT_EMPTY_RATE_THRESHOLD", f64),
+        error_rate_threshold: get_env_with_parsing!("DRIFT_ERROR_RATE_THRESHOLD", f64),
+        // optional, e.g. DRIFT_FIELDS_META=cv,genre,time_table
+        fields: std::env::var(format!("DRIFT_FIELDS_{}", path.to_uppercase()))
+            .map(|fields| {
+                fields
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|field| !field.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    config.validate().expect("invalid drift config");
+    config
+}
+
+#[cfg(not(feature = "rust-parser"))]
+fn builtin_parser(name: &str) -> Option<BuiltinParser> {
+    match name {
+        "meta" => Some(Box::new(ffi_parser_factory(find_meta))),
+        "detail" => Some(Box::new(ffi_parser_factory(find_detail))),
+        "tag" => Some(Box::new(ffi_parser_factory(update_tag))),
+        "idx" => Some(Box::new(max_idx_finder)),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "rust-parser")]
+fn builtin_parser(name: &str) -> Option<BuiltinParser> {
+    match name {
+        "meta" => Some(Box::new(rust_parser::find_meta)),
+        "detail" => Some(Box::new(rust_parser::find_detail)),
+        "tag" => Some(Box::new(rust_parser::update_tag)),
+        "idx" => Some(Box::new(rust_parser::find_max_idx)),
+        _ => None,
+    }
+}
+
+// `replay [--path meta] [--since 2025-01-01] [--until 2025-02-01] [--id RJ01234567] [--job-id ..]`
+// reparses archived bodies with the current parsers, nothing is fetched
+async fn run_replay(
+    args: &[String],
+    pool: Arc<Pool<RedisConnectionManager>>,
+    parse_pool: Arc<ParsePool>,
+    archive: Arc<ArchiveSink>,
+) {
+    let ReplayArgs { filter, job_id } =
+        ReplayArgs::parse(args).unwrap_or_else(|e| panic!("replay: {e}"));
+    let job_id = job_id.unwrap_or_else(|| format!("replay:{}", unix_millis(SystemTime::now())));
+    let pool_config = Arc::new(PoolAcquireConfig {
+        init_backoff: Duration::from_secs(get_env_with_parsing!("INIT_BACKOFF", u64)),
+        backoff_next: Arc::new(temp_backoff_next),
+    });
+    let paths = if filter.paths.is_empty() {
+        ["meta", "detail", "tag", "idx"]
+            .map(str::to_string)
+            .to_vec()
+    } else {
+        filter.paths.clone()
+    };
+
+    for name in paths {
+        let parser: ReplayParser = Arc::from(
+            builtin_parser(&name).unwrap_or_else(|| panic!("replay: unknown path {name}")),
+        );
+        let summary = replay_path(
+            pool.clone(),
+            pool_config.clone(),
+            ReplayContract {
+                path_name: name.clone(),
+                parser,
+                parse_pool: parse_pool.clone(),
+                archive: archive.clone(),
+                filter: filter.clone(),
+                fallback_encoding: fallback_encoding(&name),
+                job_id: job_id.clone(),
+                result_keyword: get_env!("RESULT_KEYWORD"),
+                storage_time: get_env_with_parsing!("STORAGE_TIME", usize),
+                // as the live detail path
+                flag_new_entries: name == "detail",
+                drift: drift_config(&name),
+                inner_buf: get_env_with_parsing!("INNER_PATH_BUFFER", usize),
+            },
+        )
+        .await
+        .unwrap_or_else(|e| panic!("replay of {name} failed: {e}"));
+        tracing::info!(
+            path = %name,
+            job_id,
+            replayed = summary.replayed,
+            parse_failed = summary.parse_failed,
+            skipped = summary.skipped,
+            "replay done"
+        );
+    }
+}
+
 #[tokio::main]
 async fn main() {
+    // child process of a WorkerPool, see parser::worker
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some(PARSER_WORKER_ARG) {
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+            .init();
+        #[cfg(feature = "ffi-parser")]
+        init_ffi_logging();
+        // the environment is inherited from the service, .env included
+        init_meta_timezone_from_env();
+
+        let name = args.get(2).map(String::as_str).unwrap_or_default();
+        let parser = builtin_parser(name).expect("unknown parser for parser worker");
+        let _span = tracing::info_span!("parser_worker", path = %name).entered();
+        run_parser_worker(parser).expect("parser worker failed");
+        return;
+    }
+
     tracing_subscriber::fmt()
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
+    #[cfg(feature = "ffi-parser")]
+    init_ffi_logging();
     dotenv::dotenv().expect("failied dotenv::dotenv");
+    init_meta_timezone_from_env();
     // create request fetch cont;ract
 
     let channel_buf = get_env_with_parsing!("CHANNEL_BUF", usize);
@@ -67,11 +303,181 @@ async fn main() {
         req_fetch_contract_create_macro!(get_env!("IDX_REQUEST_Q_KEYWORD"));
 
     // ready parser
-    let retry = get_env_with_parsing!("NET_REQUEST_RETRY", i32);
-    let meta_scraper = generate_scraper(ffi_parser_factory(find_meta), retry);
-    let detail_scraper = generate_scraper(ffi_parser_factory(find_detail), retry);
-    let tag_update_scraper = generate_scraper(ffi_parser_factory(update_tag), retry);
-    let idx_scraper = generate_scraper(max_idx_finder, retry);
+    let parse_concurrency = get_env_with_parsing!("PARSE_CONCURRENCY", usize);
+    // no permit, no parse, every scrape would wait forever
+    assert!(parse_concurrency > 0, "PARSE_CONCURRENCY must be positive");
+    let parse_pool = Arc::new(ParsePool::new(parse_concurrency));
+
+    let manager =
+        RedisConnectionManager::new(get_env!("REDIS_URL")).expect("failed to create redis manager");
+    let pool = Arc::new(
+        Pool::builder()
+            .max_size(get_env_with_parsing!("MAX_POOL_SIZE", u32))
+            .connection_timeout(Duration::from_secs(get_env_with_parsing!(
+                "CONNECTION_TIMEOUT",
+                u64
+            )))
+            .build(manager)
+            .await
+            .expect("failed build pool"),
+    );
+
+    // one for the process, all paths share the budget of a host
+    let local_rate_limiter = HostRateLimiter::new(
+        get_env_with_parsing!("RATE_LIMIT_RPS", f64),
+        get_env_with_parsing!("RATE_LIMIT_BURST", u32),
+    );
+    // CLUSTER_RATE_LIMIT_RPS is one budget for every replica, kept in redis
+    // the local limiter is then the fallback while redis is unreachable
+    let rate_limiter = Arc::new(match std::env::var("CLUSTER_RATE_LIMIT_RPS") {
+        Ok(rps) => {
+            let rps = rps
+                .parse::<f64>()
+                .expect("failed to parse CLUSTER_RATE_LIMIT_RPS");
+            assert!(rps > 0.0, "CLUSTER_RATE_LIMIT_RPS must be positive");
+            RateLimiter::Cluster(ClusterRateLimiter::new(
+                pool.clone(),
+                get_env!("CLUSTER_RATE_LIMIT_KEY"),
+                rps,
+                get_env_with_parsing!("CLUSTER_RATE_LIMIT_BURST", u32),
+                Duration::from_millis(get_env_with_parsing!("CLUSTER_RATE_LIMIT_TIMEOUT_MS", u64)),
+                local_rate_limiter,
+            ))
+        }
+        Err(_) => RateLimiter::Local(local_rate_limiter),
+    });
+
+    // ROBOTS_TXT=respect checks every url against the robots.txt of its host first
+    let robots = std::env::var("ROBOTS_TXT")
+        .is_ok_and(|mode| mode == "respect")
+        .then(|| {
+            Arc::new(RobotsPolicy {
+                client: http_client_config("robots")
+                    .build()
+                    .expect("failed to build robots.txt client"),
+                user_agent: get_env!("ROBOTS_USER_AGENT"),
+                key_prefix: get_env!("ROBOTS_CACHE_KEY"),
+                cache_ttl: get_env_with_parsing!("ROBOTS_CACHE_TTL", usize),
+                rate_limiter: rate_limiter.clone(),
+            })
+        });
+
+    // CONDITIONAL_GET=on revalidates recrawls with the ETag / Last-Modified of the last fetch
+    let conditional = std::env::var("CONDITIONAL_GET")
+        .is_ok_and(|mode| mode == "on")
+        .then(|| {
+            Arc::new(ConditionalGet {
+                key_prefix: get_env!("CONDITIONAL_GET_KEY"),
+                ttl: get_env_with_parsing!("CONDITIONAL_GET_TTL", usize),
+            })
+        });
+
+    // ARCHIVE=disk|redis keeps the raw body of every fetch for ARCHIVE_RETENTION_SECS
+    let archive = std::env::var("ARCHIVE").ok().map(|mode| {
+        let store = match mode.as_str() {
+            "disk" => ArchiveStore::Disk {
+                dir: get_env!("ARCHIVE_DIR").into(),
+            },
+            "redis" => ArchiveStore::Redis {
+                pool: pool.clone(),
+                key_prefix: get_env!("ARCHIVE_KEY"),
+            },
+            _ => panic!("unknown ARCHIVE: {mode}"),
+        };
+        let retention = get_env_with_parsing!("ARCHIVE_RETENTION_SECS", u64);
+        // redis rejects EX 0, the pruner would remove each record right away
+        assert!(retention > 0, "ARCHIVE_RETENTION_SECS must be positive");
+        Arc::new(ArchiveSink {
+            store,
+            retention: Duration::from_secs(retention),
+            level: get_env_with_parsing!("ARCHIVE_ZSTD_LEVEL", i32),
+        })
+    });
+
+    if args.get(1).map(String::as_str) == Some(REPLAY_ARG) {
+        let archive = archive.expect("replay reads the archive, set ARCHIVE");
+        run_replay(&args[2..], pool, parse_pool, archive).await;
+        return;
+    }
+
+    // PROXY_LIST (comma separated) sends every fetch through a proxy of the pool,
+    // robots.txt is still fetched directly
+    let proxy_pool = std::env::var("PROXY_LIST").ok().and_then(|list| {
+        ProxyPool::new(
+            list.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(str::to_string)
+                .collect(),
+            get_env_with_parsing!("PROXY_SELECTION", ProxySelection),
+            get_env_with_parsing!("PROXY_MAX_FAILURES", u32),
+            Duration::from_secs(get_env_with_parsing!("PROXY_EJECTION_SECS", u64)),
+        )
+        .map(Arc::new)
+    });
+    let sticky_proxy = |path: &str| {
+        path_env("PROXY_STICKY", path)
+            .is_some_and(|value| value.parse::<bool>().expect("failed to parse PROXY_STICKY"))
+    };
+
+    // PARSER_ISOLATION=process runs the builtin parsers in child processes
+    let isolated = std::env::var("PARSER_ISOLATION").is_ok_and(|mode| mode == "process");
+    let (worker_count, worker_timeout) = if isolated {
+        let worker_count = get_env_with_parsing!("PARSER_WORKER_COUNT", usize);
+        // checkout would wait forever on an empty pool
+        assert!(worker_count > 0, "PARSER_WORKER_COUNT must be positive");
+        (
+            worker_count,
+            Duration::from_millis(get_env_with_parsing!("PARSER_WORKER_TIMEOUT_MS", u64)),
+        )
+    } else {
+        (0, Duration::ZERO)
+    };
+    macro_rules! builtin_scraper {
+        ($name:expr) => {
+            if isolated {
+                let workers = WorkerPool::new($name, worker_count, worker_timeout);
+                generate_scraper(
+                    move |html: &str| workers.parse(html),
+                    retry_policy($name),
+                    parse_pool.clone(),
+                    rate_limiter.clone(),
+                )
+            } else {
+                generate_scraper(
+                    builtin_parser($name).unwrap(),
+                    retry_policy($name),
+                    parse_pool.clone(),
+                    rate_limiter.clone(),
+                )
+            }
+        };
+    }
+    let meta_scraper = builtin_scraper!("meta");
+    let detail_scraper = builtin_scraper!("detail");
+    let tag_update_scraper = builtin_scraper!("tag");
+    let idx_scraper = builtin_scraper!("idx");
+
+    let mut metrics_set = JoinSet::new();
+    let metrics_token = CancellationToken::new();
+    invoke_parse_pool_reporter(
+        &mut metrics_set,
+        metrics_token.child_token(),
+        parse_pool.clone(),
+        Duration::from_secs(get_env_with_parsing!("PARSE_METRICS_INTERVAL", u64)),
+    );
+    if let Some(archive) = &archive {
+        invoke_archive_pruner(
+            &mut metrics_set,
+            metrics_token.child_token(),
+            archive.clone(),
+            Duration::from_secs(get_env_with_parsing!("ARCHIVE_PRUNE_INTERVAL", u64)),
+        );
+    }
+    let metrics_handler = ThreadHandler {
+        set: metrics_set,
+        token: metrics_token,
+    };
 
     // ready process req contract
     let result_keyword = get_env!("RESULT_KEYWORD");
@@ -79,8 +485,9 @@ async fn main() {
     let created_path_inner_buf = get_env_with_parsing!("INNER_PATH_BUFFER", usize);
 
     macro_rules! generate_req_contract {
-        ($scraper:expr) => {
+        ($name:expr, $scraper:expr) => {
             ProcessReqContract {
+                name: $name.to_string(),
                 result_keyword: result_keyword.clone(),
                 storage_time,
                 scraper: $scraper,
@@ -89,14 +496,26 @@ async fn main() {
                     "SEMAPHORE_SIZE",
                     usize
                 ))),
+                flag_new_entries: false,
+                drift: drift_config(&$name),
+                http_client: http_client_config(&$name),
+                fallback_encoding: fallback_encoding(&$name),
+                robots: robots.clone(),
+                proxy_pool: proxy_pool.clone(),
+                sticky_proxy: sticky_proxy(&$name),
+                conditional: conditional.clone(),
+                archive: archive.clone(),
             }
         };
     }
 
-    let process_contract_for_meta = generate_req_contract!(meta_scraper);
-    let process_contract_for_detail = generate_req_contract!(detail_scraper);
-    let process_contract_for_tag = generate_req_contract!(tag_update_scraper);
-    let process_contract_for_idx = generate_req_contract!(idx_scraper);
+    let process_contract_for_meta = generate_req_contract!("meta", meta_scraper);
+    let process_contract_for_detail = ProcessReqContract {
+        flag_new_entries: true,
+        ..generate_req_contract!("detail", detail_scraper)
+    };
+    let process_contract_for_tag = generate_req_contract!("tag", tag_update_scraper);
+    let process_contract_for_idx = generate_req_contract!("idx", idx_scraper);
 
     let redis_client =
         Arc::new(redis::Client::open(get_env!("REDIS_URL")).expect("failed to open redis client"));
@@ -106,20 +525,6 @@ async fn main() {
         backoff_next: Arc::new(temp_backoff_next),
     });
 
-    let manager =
-        RedisConnectionManager::new(get_env!("REDIS_URL")).expect("failed to create redis manager");
-    let pool = Arc::new(
-        Pool::builder()
-            .max_size(get_env_with_parsing!("MAX_POOL_SIZE", u32))
-            .connection_timeout(Duration::from_secs(get_env_with_parsing!(
-                "CONNECTION_TIMEOUT",
-                u64
-            )))
-            .build(manager)
-            .await
-            .expect("failed build pool"),
-    );
-
     let pool_config = Arc::new(PoolAcquireConfig {
         init_backoff: Duration::from_secs(get_env_with_parsing!("INIT_BACKOFF", u64)),
         backoff_next: Arc::new(temp_backoff_next),
@@ -150,8 +555,35 @@ async fn main() {
     let idx_handler = create_path_macro!(req_fetch_contract_for_idx, process_contract_for_idx)
         .expect("failed create path : idx");
 
+    // config driven paths, one per page in EXTRACTOR_CONFIG
+    let mut extractor_handlers = vec![];
+    if let Ok(config_path) = std::env::var("EXTRACTOR_CONFIG") {
+        let config = load_extractor_config(&config_path).expect("failed to load extractor config");
+        for page in config.pages {
+            let extractor = Extractor::new(&page.fields)
+                .unwrap_or_else(|e| panic!("failed to compile extractor {}: {e}", page.name));
+            let scraper = generate_scraper(
+                move |html: &str| extractor.extract(html),
+                retry_policy(&page.name),
+                parse_pool.clone(),
+                rate_limiter.clone(),
+            );
+
+            let handler = create_path_macro!(
+                req_fetch_contract_create_macro!(page.req_q_keyword),
+                generate_req_contract!(page.name, scraper)
+            )
+            .unwrap_or_else(|_| panic!("failed create path : {}", page.name));
+            extractor_handlers.push(handler);
+        }
+    }
+
     meta_handler.join().await;
     detail_handler.join().await;
     tag_updater_handler.join().await;
     idx_handler.join().await;
+    for handler in extractor_handlers {
+        handler.join().await;
+    }
+    metrics_handler.stop().await;
 }
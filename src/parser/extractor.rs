@@ -0,0 +1,204 @@
+// config driven extractor
+// a page type is described by css selectors instead of a hand written gumbo walk, e.g.
+//
+// {
+//   "pages": [{
+//     "name": "circle",
+//     "req_q_keyword": "circle_req:queue",
+//     "fields": [
+//       { "name": "name", "selector": "h1.circle-name", "required": true },
+//       { "name": "id", "selector": "link[rel=canonical]", "attr": "href", "regex": "circle/([^/]+)" },
+//       { "name": "works", "selector": "div.post-list > a", "multiple": true, "fields": [
+//         { "name": "url", "selector": ":scope", "attr": "href" },
+//         { "name": "title", "selector": ":scope" }
+//       ]}
+//     ]
+//   }]
+// }
+//
+// - selector : css selector, relative to the parent group (or the document)
+// - attr     : attribute to extract, trimmed inner text when omitted
+// - regex    : keep the first capture group (or the whole match), no match means missing
+// - multiple : collect every match into an array instead of the first one
+// - fields   : turn each match into an object of sub fields
+// - required : fail the whole parse when the field is missing
+use std::path::Path;
+
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{Map, Value};
+
+use crate::parser::ParserErr;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ExtractorErr {
+    #[error("{0}")]
+    IoErr(#[from] std::io::Error),
+
+    #[error("{0}")]
+    SerdeJsonErr(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    RegexErr(#[from] regex::Error),
+
+    #[error("invalid selector for {field}: {selector}")]
+    SelectorErr { field: String, selector: String },
+}
+
+#[derive(serde::Deserialize)]
+pub struct ExtractorConfig {
+    pub pages: Vec<PageConfig>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PageConfig {
+    pub name: String,
+    pub req_q_keyword: String,
+    pub fields: Vec<FieldRule>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FieldRule {
+    pub name: String,
+    pub selector: String,
+    #[serde(default)]
+    pub attr: Option<String>,
+    #[serde(default)]
+    pub regex: Option<String>,
+    #[serde(default)]
+    pub multiple: bool,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub fields: Vec<FieldRule>,
+}
+
+pub fn load_extractor_config(path: impl AsRef<Path>) -> Result<ExtractorConfig, ExtractorErr> {
+    let raw = std::fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&raw)?)
+}
+
+struct CompiledField {
+    name: String,
+    selector: Selector,
+    attr: Option<String>,
+    regex: Option<Regex>,
+    multiple: bool,
+    required: bool,
+    fields: Vec<CompiledField>,
+}
+
+impl CompiledField {
+    fn compile(rule: &FieldRule) -> Result<Self, ExtractorErr> {
+        let selector = Selector::parse(&rule.selector).map_err(|_| ExtractorErr::SelectorErr {
+            field: rule.name.clone(),
+            selector: rule.selector.clone(),
+        })?;
+
+        Ok(CompiledField {
+            name: rule.name.clone(),
+            selector,
+            attr: rule.attr.clone(),
+            regex: rule.regex.as_deref().map(Regex::new).transpose()?,
+            multiple: rule.multiple,
+            required: rule.required,
+            fields: rule
+                .fields
+                .iter()
+                .map(CompiledField::compile)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn value_of(&self, el: ElementRef) -> Result<Option<Value>, ParserErr> {
+        if !self.fields.is_empty() {
+            return Ok(Some(extract_group(&self.fields, el)?));
+        }
+
+        let raw = match &self.attr {
+            Some(attr) => match el.value().attr(attr) {
+                Some(v) => v.trim().to_string(),
+                None => return Ok(None),
+            },
+            None => el.text().collect::<String>().trim().to_string(),
+        };
+
+        let value = match &self.regex {
+            Some(re) => match re.captures(&raw) {
+                Some(c) => c.get(1).or(c.get(0)).map(|m| m.as_str().to_string()),
+                None => None,
+            },
+            None => Some(raw),
+        };
+
+        Ok(value.map(Value::String))
+    }
+
+    // unlike ElementRef::select, the scope itself is a candidate so `:scope` works
+    fn matches<'a>(&'a self, scope: ElementRef<'a>) -> impl Iterator<Item = ElementRef<'a>> {
+        scope
+            .descendent_elements()
+            .filter(move |el| self.selector.matches_with_scope(el, Some(scope)))
+    }
+
+    fn extract(&self, scope: ElementRef) -> Result<Value, ParserErr> {
+        let value = if self.multiple {
+            let mut v = vec![];
+            for el in self.matches(scope) {
+                if let Some(value) = self.value_of(el)? {
+                    v.push(value);
+                }
+            }
+            if v.is_empty() {
+                None
+            } else {
+                Some(Value::Array(v))
+            }
+        } else {
+            let mut found = None;
+            for el in self.matches(scope) {
+                found = self.value_of(el)?;
+                if found.is_some() {
+                    break;
+                }
+            }
+            found
+        };
+
+        match value {
+            Some(value) => Ok(value),
+            None if self.required => Err(ParserErr::RequiredFieldMissing(self.name.clone())),
+            None if self.multiple => Ok(Value::Array(vec![])),
+            None => Ok(Value::Null),
+        }
+    }
+}
+
+fn extract_group(fields: &[CompiledField], scope: ElementRef) -> Result<Value, ParserErr> {
+    let mut object = Map::new();
+    for field in fields {
+        object.insert(field.name.clone(), field.extract(scope)?);
+    }
+
+    Ok(Value::Object(object))
+}
+
+pub struct Extractor {
+    fields: Vec<CompiledField>,
+}
+
+impl Extractor {
+    pub fn new(fields: &[FieldRule]) -> Result<Self, ExtractorErr> {
+        Ok(Extractor {
+            fields: fields
+                .iter()
+                .map(CompiledField::compile)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    pub fn extract(&self, html: &str) -> Result<String, ParserErr> {
+        let document = Html::parse_document(html);
+        Ok(extract_group(&self.fields, document.root_element())?.to_string())
+    }
+}
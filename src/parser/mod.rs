@@ -1,66 +1,280 @@
-use std::{
-    ffi::{CStr, CString, NulError, c_char},
-    ptr::NonNull,
-};
+pub mod extractor;
+#[cfg(feature = "rust-parser")]
+pub mod rust_parser;
+pub mod worker;
+
+#[cfg(not(any(feature = "ffi-parser", feature = "rust-parser")))]
+compile_error!("either `ffi-parser` or `rust-parser` feature is required");
+
+#[cfg(feature = "ffi-parser")]
+use std::{ffi::c_char, ptr::NonNull};
+
+// return codes of the extern "C" parsers
+// keep in sync with ParseCode in cpp/include/gumbo_search_lib.hpp
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseFailure {
+    #[error("gumbo failed to parse the document")]
+    GumboFailed,
+
+    #[error("div#main not found")]
+    MainNotFound,
+
+    #[error("link[rel=canonical] not found")]
+    CanonicalUrlNotFound,
+
+    #[error("title not found")]
+    TitleNotFound,
+
+    #[error("div#post-time not found")]
+    PostTimeNotFound,
+
+    #[error("work id not found in canonical url")]
+    WorkIdNotFound,
+
+    #[error("div.tag-list not found")]
+    TagListNotFound,
+
+    // no longer returned, find_max_idx reports a single page instead
+    #[error("div.wp-pagenavi not found")]
+    PagenaviNotFound,
+
+    #[error("page count not found in div.wp-pagenavi")]
+    PageCountNotFound,
+
+    #[error("div#post-time present but unparseable")]
+    PostTimeUnparseable,
+
+    #[error("exception thrown while parsing")]
+    Exception,
+
+    #[error("unknown parser return code: {0}")]
+    Unknown(i32),
+}
+
+impl ParseFailure {
+    pub fn from_code(code: i32) -> Self {
+        match code {
+            1 => ParseFailure::GumboFailed,
+            2 => ParseFailure::MainNotFound,
+            3 => ParseFailure::CanonicalUrlNotFound,
+            4 => ParseFailure::TitleNotFound,
+            5 => ParseFailure::PostTimeNotFound,
+            6 => ParseFailure::WorkIdNotFound,
+            7 => ParseFailure::TagListNotFound,
+            8 => ParseFailure::PagenaviNotFound,
+            9 => ParseFailure::PageCountNotFound,
+            10 => ParseFailure::PostTimeUnparseable,
+            99 => ParseFailure::Exception,
+            other => ParseFailure::Unknown(other),
+        }
+    }
+
+    pub fn as_code(&self) -> i32 {
+        match self {
+            ParseFailure::GumboFailed => 1,
+            ParseFailure::MainNotFound => 2,
+            ParseFailure::CanonicalUrlNotFound => 3,
+            ParseFailure::TitleNotFound => 4,
+            ParseFailure::PostTimeNotFound => 5,
+            ParseFailure::WorkIdNotFound => 6,
+            ParseFailure::TagListNotFound => 7,
+            ParseFailure::PagenaviNotFound => 8,
+            ParseFailure::PageCountNotFound => 9,
+            ParseFailure::PostTimeUnparseable => 10,
+            ParseFailure::Exception => 99,
+            ParseFailure::Unknown(code) => *code,
+        }
+    }
+
+    // stable code sent to producers through RedisResponse
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseFailure::GumboFailed => "gumbo_failed",
+            ParseFailure::MainNotFound => "main_not_found",
+            ParseFailure::CanonicalUrlNotFound => "canonical_url_not_found",
+            ParseFailure::TitleNotFound => "title_not_found",
+            ParseFailure::PostTimeNotFound => "post_time_not_found",
+            ParseFailure::WorkIdNotFound => "work_id_not_found",
+            ParseFailure::TagListNotFound => "tag_list_not_found",
+            ParseFailure::PagenaviNotFound => "pagenavi_not_found",
+            ParseFailure::PageCountNotFound => "page_count_not_found",
+            ParseFailure::PostTimeUnparseable => "post_time_unparseable",
+            ParseFailure::Exception => "parser_exception",
+            ParseFailure::Unknown(_) => "parser_unknown",
+        }
+    }
+}
 
 #[derive(thiserror::Error, Debug)]
 pub enum ParserErr {
     #[error("{0}")]
-    NulErr(#[from] NulError),
-
-    #[error("{0}")]
-    FFICallErr(i32),
+    ParseFailure(#[from] ParseFailure),
 
     #[error("")]
     NonNulIsNoneErr,
+
+    #[error("required field is missing: {0}")]
+    RequiredFieldMissing(String),
+
+    #[error("{0}")]
+    WorkerIoErr(#[from] std::io::Error),
+
+    #[error("parser worker crashed: {0}")]
+    WorkerCrashed(String),
+
+    #[error("parser worker timed out")]
+    WorkerTimeout,
+
+    #[error("parser worker failed: {0}")]
+    WorkerErr(String),
+}
+
+impl ParserErr {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserErr::ParseFailure(failure) => failure.code(),
+            ParserErr::NonNulIsNoneErr => "empty_parser_result",
+            ParserErr::RequiredFieldMissing(_) => "required_field_missing",
+            ParserErr::WorkerIoErr(_) => "parser_worker_io",
+            ParserErr::WorkerCrashed(_) => "parser_worker_crashed",
+            ParserErr::WorkerTimeout => "parser_worker_timeout",
+            ParserErr::WorkerErr(_) => "parser_worker_failed",
+        }
+    }
 }
 
+// html is passed as pointer + length (no NUL terminator needed, embedded NULs are fine)
+// and the result comes back as a malloc'd buffer + length, released with free_char
+#[cfg(feature = "ffi-parser")]
 unsafe extern "C" {
-    pub fn find_meta(html: *const c_char, result: *mut *mut c_char) -> i32;
-    pub fn find_detail(html: *const c_char, result: *mut *mut c_char) -> i32;
-    pub fn update_tag(html: *const c_char, result: *mut *mut c_char) -> i32;
-    pub fn find_max_idx(html: *const c_char, result: *mut i32) -> i32;
+    pub fn find_meta(
+        html: *const c_char,
+        html_len: usize,
+        result: *mut *mut c_char,
+        result_len: *mut usize,
+    ) -> i32;
+    pub fn find_detail(
+        html: *const c_char,
+        html_len: usize,
+        result: *mut *mut c_char,
+        result_len: *mut usize,
+    ) -> i32;
+    pub fn update_tag(
+        html: *const c_char,
+        html_len: usize,
+        result: *mut *mut c_char,
+        result_len: *mut usize,
+    ) -> i32;
+    pub fn find_max_idx(
+        html: *const c_char,
+        html_len: usize,
+        result: *mut *mut c_char,
+        result_len: *mut usize,
+    ) -> i32;
     pub fn free_char(ptr: *mut c_char);
+    pub fn set_log_callback(
+        callback: unsafe extern "C" fn(i32, *const c_char, usize, *const c_char, usize),
+    );
+    pub fn set_meta_utc_offset(minutes: i32);
 }
 
-pub fn max_idx_finder(html: &str) -> Result<String, ParserErr> {
-    let html_cchar = CString::new(html)?;
-    let mut max_idx: i32 = -1;
-    let ffi_result: i32;
+// receives log_msg from the c++ side, events inherit the span of the parsing thread
+#[cfg(feature = "ffi-parser")]
+unsafe extern "C" fn log_from_cpp(
+    level: i32,
+    parser: *const c_char,
+    parser_len: usize,
+    msg: *const c_char,
+    msg_len: usize,
+) {
+    let (parser, msg) = unsafe {
+        (
+            String::from_utf8_lossy(std::slice::from_raw_parts(parser.cast::<u8>(), parser_len)),
+            String::from_utf8_lossy(std::slice::from_raw_parts(msg.cast::<u8>(), msg_len)),
+        )
+    };
+
+    match level {
+        1 => tracing::error!(target: "cpp", parser = %parser, "{msg}"),
+        2 => tracing::warn!(target: "cpp", parser = %parser, "{msg}"),
+        3 => tracing::info!(target: "cpp", parser = %parser, "{msg}"),
+        4 => tracing::debug!(target: "cpp", parser = %parser, "{msg}"),
+        _ => tracing::trace!(target: "cpp", parser = %parser, "{msg}"),
+    }
+}
 
+#[cfg(feature = "ffi-parser")]
+pub fn init_ffi_logging() {
+    unsafe { set_log_callback(log_from_cpp) };
+}
+
+// "+09:00", "-05:30", "+0900", "Z" or "UTC" ---> offset in minutes
+pub fn parse_utc_offset(s: &str) -> Option<i32> {
+    let s = s.trim();
+    if s.eq_ignore_ascii_case("z") || s.eq_ignore_ascii_case("utc") {
+        return Some(0);
+    }
+
+    let (sign, rest) = match s.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some(hm) => hm,
+        None => rest.split_at_checked(2)?,
+    };
+    let (hours, minutes): (i32, i32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    Some(sign * (hours * 60 + minutes))
+}
+
+// timezone of the wall time in div#post-time, applied to every enabled backend
+pub fn init_meta_timezone(minutes: i32) {
+    #[cfg(feature = "ffi-parser")]
     unsafe {
-        ffi_result = find_max_idx(html_cchar.as_ptr(), &mut max_idx);
+        set_meta_utc_offset(minutes)
     };
+    #[cfg(feature = "rust-parser")]
+    rust_parser::set_meta_utc_offset(minutes);
+}
 
-    if ffi_result == 0 {
-        Ok(format!("{}", max_idx))
-    } else {
-        Err(ParserErr::FFICallErr(ffi_result))
-    }
+// {"current", "total", "next_url", "prev_url", "page_url_pattern", "single_page"}
+#[cfg(feature = "ffi-parser")]
+pub fn max_idx_finder(html: &str) -> Result<String, ParserErr> {
+    ffi_parser_factory(find_max_idx)(html)
 }
 
+#[cfg(feature = "ffi-parser")]
 pub fn ffi_parser_factory(
-    ffi_func: unsafe extern "C" fn(*const c_char, *mut *mut c_char) -> i32,
+    ffi_func: unsafe extern "C" fn(*const c_char, usize, *mut *mut c_char, *mut usize) -> i32,
 ) -> impl Fn(&str) -> Result<String, ParserErr> {
     move |html_str: &str| {
-        let html_cchar = CString::new(html_str)?;
-        let mut result_json = std::ptr::null_mut();
+        let mut result_buf = std::ptr::null_mut();
+        let mut result_len: usize = 0;
 
         unsafe {
-            let ffi_result = ffi_func(html_cchar.as_ptr(), &mut result_json);
-            match NonNull::new(result_json) {
-                Some(n) => {
-                    let result = CStr::from_ptr(n.as_ptr()).to_string_lossy().to_string();
-                    free_char(result_json);
-
-                    if ffi_result == 0 {
-                        Ok(result)
-                    } else {
-                        Err(ParserErr::FFICallErr(ffi_result))
-                    }
-                }
-
-                None => Err(ParserErr::NonNulIsNoneErr),
+            let ffi_result = ffi_func(
+                html_str.as_ptr().cast(),
+                html_str.len(),
+                &mut result_buf,
+                &mut result_len,
+            );
+            let result = NonNull::new(result_buf).map(|n| {
+                let bytes = std::slice::from_raw_parts(n.as_ptr().cast::<u8>(), result_len);
+                let result = String::from_utf8_lossy(bytes).into_owned();
+                free_char(n.as_ptr());
+                result
+            });
+
+            // the failure code wins over a missing buffer, most failures return without one
+            match (ffi_result, result) {
+                (0, Some(result)) => Ok(result),
+                (0, None) => Err(ParserErr::NonNulIsNoneErr),
+                (code, _) => Err(ParseFailure::from_code(code).into()),
             }
         }
     }
@@ -0,0 +1,780 @@
+// pure rust counterparts of cpp/src/*.cpp
+// every function walks the tree the same way as its c++ twin and fails the same way,
+// so the two backends can be swapped without producers noticing.
+use std::sync::{
+    LazyLock,
+    atomic::{AtomicI32, Ordering},
+};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat};
+use regex::Regex;
+use scraper::{ElementRef, Html};
+use serde_json::{Value, json};
+
+use crate::parser::{ParseFailure, ParserErr};
+
+static WORK_ID_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"rj[\d]+").unwrap());
+static PAGES_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(\d+) / (\d+)").unwrap());
+static PAGE_NUMBER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(\d+)\s*$").unwrap());
+// same order as POST_TIME_FORMATS in cpp/src/find_meta.cpp, groups are y, m, d, H?, M?
+static POST_TIME_RES: LazyLock<Vec<Regex>> = LazyLock::new(|| {
+    [
+        // %Y年%m月%d日%H時
+        r"^(\d{1,4})年(\d{1,2})月(\d{1,2})日(\d{1,2})時",
+        // %Y年%m月%d日 %H:%M
+        r"^(\d{1,4})年(\d{1,2})月(\d{1,2})日[ \t\n\r\x0b\x0c]*(\d{1,2}):(\d{1,2})",
+        // %Y-%m-%dT%H:%M
+        r"^(\d{1,4})-(\d{1,2})-(\d{1,2})T(\d{1,2}):(\d{1,2})",
+        // %Y-%m-%d %H:%M
+        r"^(\d{1,4})-(\d{1,2})-(\d{1,2})[ \t\n\r\x0b\x0c]*(\d{1,2}):(\d{1,2})",
+        // %Y/%m/%d %H:%M
+        r"^(\d{1,4})/(\d{1,2})/(\d{1,2})[ \t\n\r\x0b\x0c]*(\d{1,2}):(\d{1,2})",
+        // %Y年%m月%d日
+        r"^(\d{1,4})年(\d{1,2})月(\d{1,2})日",
+        // %Y-%m-%d
+        r"^(\d{1,4})-(\d{1,2})-(\d{1,2})",
+        // %Y/%m/%d
+        r"^(\d{1,4})/(\d{1,2})/(\d{1,2})",
+    ]
+    .into_iter()
+    .map(|re| Regex::new(re).unwrap())
+    .collect()
+});
+static CLOCK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:([0-9]{1,9}):)?([0-9]{1,9}):([0-9]{1,9})$").unwrap());
+static DURATION_UNITS_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:([0-9]{1,9})時間)?(?:([0-9]{1,9})分)?(?:([0-9]{1,9})秒)?$").unwrap()
+});
+
+// utc offset (minutes) of the wall time written in div#post-time, JST unless set at startup
+static META_UTC_OFFSET: AtomicI32 = AtomicI32::new(540);
+
+pub fn set_meta_utc_offset(minutes: i32) {
+    META_UTC_OFFSET.store(minutes, Ordering::Relaxed);
+}
+
+fn has_id(el: &ElementRef, id: &str) -> bool {
+    el.value().attr("id") == Some(id)
+}
+
+// class is a whitespace separated token list, as has_class in cpp/src/lib.cpp
+fn has_class(el: &ElementRef, class: &str) -> bool {
+    el.value()
+        .attr("class")
+        .is_some_and(|classes| classes.split_ascii_whitespace().any(|c| c == class))
+}
+
+fn is_tag(el: &ElementRef, tag: &str) -> bool {
+    el.value().name() == tag
+}
+
+// gumbo splits text nodes into TEXT and WHITESPACE
+fn is_whitespace(text: &str) -> bool {
+    text.chars()
+        .all(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c'))
+}
+
+fn inner_text(el: &ElementRef, out: &mut String) {
+    if is_tag(el, "script") || is_tag(el, "style") {
+        return;
+    }
+
+    for child in el.children() {
+        if let Some(text) = child.value().as_text() {
+            out.push_str(text);
+        } else if let Some(child_el) = ElementRef::wrap(child) {
+            inner_text(&child_el, out);
+        }
+    }
+}
+
+fn find_first<'a>(
+    el: ElementRef<'a>,
+    pred: &impl Fn(&ElementRef) -> bool,
+) -> Option<ElementRef<'a>> {
+    if pred(&el) {
+        return Some(el);
+    }
+    el.child_elements()
+        .find_map(|child| find_first(child, pred))
+}
+
+fn trim(text: &str) -> &str {
+    text.trim_matches(|c| matches!(c, ' ' | '\t' | '\n' | '\r' | '\x0c'))
+}
+
+// https://example.com/rj01234567/ ---> rj01234567
+fn find_id_from_url(url: &str) -> Option<String> {
+    WORK_ID_RE.find(url).map(|m| m.as_str().to_string())
+}
+
+// same rules as resolve_url in cpp/src/lib.cpp, dot segments are kept as they are
+fn resolve_url(base: &str, href: &str) -> String {
+    let is_absolute = href
+        .find("://")
+        .is_some_and(|scheme_end| href.find('/').is_none_or(|slash| scheme_end < slash));
+    if base.is_empty() || is_absolute {
+        return href.to_string();
+    }
+
+    let Some(base_scheme_end) = base.find("://") else {
+        return href.to_string();
+    };
+
+    if href.starts_with("//") {
+        return format!("{}{}", &base[..base_scheme_end + 1], href);
+    }
+
+    let origin = match base[base_scheme_end + 3..].find('/') {
+        Some(path_begin) => &base[..base_scheme_end + 3 + path_begin],
+        None => base,
+    };
+    let without_fragment = base.split('#').next().unwrap_or_default();
+    let without_query = without_fragment.split('?').next().unwrap_or_default();
+
+    match href.chars().next() {
+        None => without_fragment.to_string(),
+        Some('/') => format!("{origin}{href}"),
+        Some('#') => format!("{without_fragment}{href}"),
+        Some('?') => format!("{without_query}{href}"),
+        _ if without_query.len() <= origin.len() => format!("{origin}/{href}"),
+        _ => {
+            let dir_end = without_query.rfind('/').map_or(0, |i| i + 1);
+            format!("{}{}", &without_query[..dir_end], href)
+        }
+    }
+}
+
+fn tag_json(name: String, url: String) -> Value {
+    json!({ "name": name, "url": url })
+}
+
+// <base href> wins over <link rel="canonical">, as in browsers
+fn find_base_url(document: &Html) -> String {
+    let root = document.root_element();
+    let has_href = |el: &ElementRef| el.value().attr("href").is_some_and(|h| !h.is_empty());
+    let base = find_first(root, &|el| is_tag(el, "base") && has_href(el));
+    let canonical = find_first(root, &|el| {
+        is_tag(el, "link") && el.value().attr("rel") == Some("canonical") && has_href(el)
+    });
+
+    base.or(canonical)
+        .and_then(|el| el.value().attr("href"))
+        .unwrap_or_default()
+        .to_string()
+}
+
+pub fn find_detail(html: &str) -> Result<String, ParserErr> {
+    let document = Html::parse_document(html);
+    let main = find_first(document.root_element(), &|el| {
+        is_tag(el, "div") && has_id(el, "main")
+    })
+    .ok_or(ParseFailure::MainNotFound)?;
+    let base = find_base_url(&document);
+
+    // the last post-list wins, as in find_detail_entries
+    let post_list = main
+        .child_elements()
+        .filter(|el| is_tag(el, "div") && has_class(el, "post-list"))
+        .last();
+
+    let entries: Vec<Value> = match post_list {
+        Some(post_list) => post_list
+            .child_elements()
+            .filter(|el| is_tag(el, "a"))
+            .filter_map(|el| {
+                let url = resolve_url(&base, el.value().attr("href")?);
+
+                let mut text = String::new();
+                inner_text(&el, &mut text);
+
+                let thumbnail = find_first(el, &|el| {
+                    is_tag(el, "img") && el.value().attr("src").is_some_and(|src| !src.is_empty())
+                })
+                .and_then(|img| img.value().attr("src"))
+                .map(|src| resolve_url(&base, src));
+
+                Some(json!({
+                    "id": find_id_from_url(&url),
+                    "title": trim(&text),
+                    "url": url,
+                    "thumbnail": thumbnail,
+                }))
+            })
+            .collect(),
+        None => vec![],
+    };
+
+    Ok(Value::Array(entries).to_string())
+}
+
+pub fn update_tag(html: &str) -> Result<String, ParserErr> {
+    let document = Html::parse_document(html);
+    let main = find_first(document.root_element(), &|el| {
+        is_tag(el, "div") && has_id(el, "main")
+    })
+    .ok_or(ParseFailure::MainNotFound)?;
+
+    let tag_list = main
+        .child_elements()
+        .filter(|el| is_tag(el, "div") && has_class(el, "tag-list"))
+        .last()
+        .ok_or(ParseFailure::TagListNotFound)?;
+
+    let tags: Vec<Value> = tag_list
+        .child_elements()
+        .filter(|el| is_tag(el, "a"))
+        .filter_map(|el| {
+            let href = el.value().attr("href")?;
+            let text = el
+                .children()
+                .filter_map(|child| child.value().as_text())
+                .filter(|text| !is_whitespace(text))
+                .last()
+                .map(|text| text.to_string())
+                .unwrap_or_default();
+
+            Some(tag_json(text, href.to_string()))
+        })
+        .collect();
+
+    Ok(Value::Array(tags).to_string())
+}
+
+// container ---> main ---> wp-pagenavi ---> pages / current / links
+fn find_main_in_container<'a>(el: ElementRef<'a>) -> Option<ElementRef<'a>> {
+    if is_tag(&el, "div") && has_id(&el, "container") {
+        let main = el
+            .child_elements()
+            .find(|inner| is_tag(inner, "div") && has_id(inner, "main"));
+        if main.is_some() {
+            return main;
+        }
+    }
+
+    el.child_elements().find_map(find_main_in_container)
+}
+
+struct Pagination {
+    current: i32,
+    total: i32,
+    next_url: Option<String>,
+    prev_url: Option<String>,
+    // e.g. "https://example.com/page/{}/"
+    page_url_pattern: Option<String>,
+    single_page: bool,
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Pagination {
+            current: 1,
+            total: 1,
+            next_url: None,
+            prev_url: None,
+            page_url_pattern: None,
+            single_page: true,
+        }
+    }
+}
+
+// ".../page/4/" with page 4 ---> ".../page/{}/"
+fn to_page_pattern(href: &str, page: i32) -> Option<String> {
+    let n = page.to_string();
+    let pos = href.rfind(&n)?;
+    Some(format!("{}{{}}{}", &href[..pos], &href[pos + n.len()..]))
+}
+
+fn parse_page_number(text: &str) -> Option<i32> {
+    PAGE_NUMBER_RE
+        .captures(text)
+        .and_then(|c| c[1].parse().ok())
+}
+
+fn find_page(main: ElementRef) -> Result<Pagination, ParseFailure> {
+    let mut p = Pagination::default();
+    // no wp-pagenavi means everything fits on one page
+    let Some(pagenavi) = main
+        .child_elements()
+        .find(|el| is_tag(el, "div") && has_class(el, "wp-pagenavi"))
+    else {
+        return Ok(p);
+    };
+
+    p.single_page = false;
+    let mut got_total = false;
+    for el in pagenavi.child_elements() {
+        let mut text = String::new();
+        inner_text(&el, &mut text);
+
+        if has_class(&el, "pages") {
+            let c = PAGES_RE
+                .captures(&text)
+                .ok_or(ParseFailure::PageCountNotFound)?;
+            p.current = c[1].parse().map_err(|_| ParseFailure::PageCountNotFound)?;
+            p.total = c[2].parse().map_err(|_| ParseFailure::PageCountNotFound)?;
+            got_total = true;
+        } else if is_tag(&el, "span") && has_class(&el, "current") {
+            if let Some(n) = parse_page_number(&text) {
+                p.current = n;
+            }
+        } else if is_tag(&el, "a") {
+            let Some(href) = el.value().attr("href") else {
+                continue;
+            };
+
+            if has_class(&el, "previouspostslink") {
+                p.prev_url = Some(href.to_string());
+            } else if has_class(&el, "nextpostslink") {
+                p.next_url = Some(href.to_string());
+            }
+
+            if p.page_url_pattern.is_none()
+                && let Some(n) = parse_page_number(&text)
+            {
+                p.page_url_pattern = to_page_pattern(href, n);
+            }
+        }
+    }
+
+    if !got_total {
+        return Err(ParseFailure::PageCountNotFound);
+    }
+    if p.page_url_pattern.is_none()
+        && let Some(next_url) = &p.next_url
+    {
+        p.page_url_pattern = to_page_pattern(next_url, p.current + 1);
+    }
+
+    Ok(p)
+}
+
+pub fn find_max_idx(html: &str) -> Result<String, ParserErr> {
+    let document = Html::parse_document(html);
+    let main = find_main_in_container(document.root_element()).ok_or(ParseFailure::MainNotFound)?;
+    let p = find_page(main)?;
+
+    Ok(json!({
+        "current": p.current,
+        "total": p.total,
+        "next_url": p.next_url,
+        "prev_url": p.prev_url,
+        "page_url_pattern": p.page_url_pattern,
+        "single_page": p.single_page,
+    })
+    .to_string())
+}
+
+// schema version of the find_meta output, bump on any breaking change
+// 2: scenario, description, price, file_size, sample_images, og fallbacks, site suffix stripped from title
+// 3: time is the real epoch of the post-time wall clock (was read as utc), time_iso added
+// 4: time_table entries carry start / duration / unparseable, runtime added
+const META_SCHEMA_VERSION: i32 = 4;
+
+#[derive(Default)]
+struct MetaWalk<'a> {
+    title: Option<String>,
+    url: Option<String>,
+    img: Option<String>,
+    time: Option<String>,
+    description: Option<String>,
+    price: Option<String>,
+    file_size: Option<String>,
+    runtime: Option<String>,
+    post_tag: Option<ElementRef<'a>>,
+    chapter: Option<ElementRef<'a>>,
+    sample: Option<ElementRef<'a>>,
+
+    og_title: Option<String>,
+    og_url: Option<String>,
+    og_image: Option<String>,
+    og_description: Option<String>,
+    og_site_name: Option<String>,
+}
+
+impl<'a> MetaWalk<'a> {
+    fn is_done(&self) -> bool {
+        self.title.is_some()
+            && self.url.is_some()
+            && self.img.is_some()
+            && self.time.is_some()
+            && self.description.is_some()
+            && self.price.is_some()
+            && self.file_size.is_some()
+            && self.runtime.is_some()
+            && self.post_tag.is_some()
+            && self.chapter.is_some()
+            && self.sample.is_some()
+            && self.og_title.is_some()
+            && self.og_url.is_some()
+            && self.og_image.is_some()
+            && self.og_description.is_some()
+            && self.og_site_name.is_some()
+    }
+
+    fn walk(&mut self, el: ElementRef<'a>) {
+        if self.url.is_none() && is_tag(&el, "link") && el.value().attr("rel") == Some("canonical")
+        {
+            self.url = el.value().attr("href").map(str::to_string);
+        }
+
+        if is_tag(&el, "meta")
+            && let Some(content) = el.value().attr("content")
+        {
+            let og = match el.value().attr("property") {
+                Some("og:title") => Some(&mut self.og_title),
+                Some("og:url") => Some(&mut self.og_url),
+                Some("og:image") => Some(&mut self.og_image),
+                Some("og:description") => Some(&mut self.og_description),
+                Some("og:site_name") => Some(&mut self.og_site_name),
+                _ => None,
+            };
+            // empty content does not count as found, as in the c++ walk
+            if let Some(og) = og.filter(|og| og.is_none())
+                && !content.is_empty()
+            {
+                *og = Some(content.to_string());
+            }
+
+            if self.description.is_none() && el.value().attr("name") == Some("description") {
+                self.description = Some(content.to_string());
+            }
+        }
+
+        if self.title.is_none() && is_tag(&el, "title") {
+            let mut title = String::new();
+            inner_text(&el, &mut title);
+            self.title = Some(title);
+        }
+
+        if self.img.is_none() && is_tag(&el, "video") {
+            self.img = el.value().attr("poster").map(str::to_string);
+        }
+
+        if self.time.is_none() && is_tag(&el, "div") && has_id(&el, "post-time") {
+            let mut out = String::new();
+            inner_text(&el, &mut out);
+            self.time = Some(trim(&out).to_string());
+        }
+
+        if self.price.is_none() && is_tag(&el, "div") && has_id(&el, "post-price") {
+            let mut out = String::new();
+            inner_text(&el, &mut out);
+            self.price = Some(out);
+        }
+
+        if self.file_size.is_none() && is_tag(&el, "div") && has_id(&el, "post-size") {
+            let mut out = String::new();
+            inner_text(&el, &mut out);
+            self.file_size = Some(trim(&out).to_string());
+        }
+
+        if self.runtime.is_none() && is_tag(&el, "div") && has_id(&el, "post-runtime") {
+            let mut out = String::new();
+            inner_text(&el, &mut out);
+            self.runtime = Some(out);
+        }
+
+        if is_tag(&el, "div") && has_id(&el, "post-tag") {
+            self.post_tag = Some(el);
+        }
+        if is_tag(&el, "div") && has_id(&el, "chapter") {
+            self.chapter = Some(el);
+        }
+        if is_tag(&el, "div") && has_id(&el, "sample") {
+            self.sample = Some(el);
+        }
+
+        if self.is_done() {
+            return;
+        }
+
+        for child in el.child_elements() {
+            self.walk(child);
+        }
+    }
+}
+
+// "1,320円" ---> 1320, no digits (or too many for i64) ---> None
+fn parse_price(text: &str) -> Option<i64> {
+    let digits: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+    if digits.is_empty() || digits.len() > 18 {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+// "work title | site name" ---> "work title"
+// with og:site_name the suffix has to be exactly the site name, otherwise only " | " is treated as a separator
+fn strip_site_suffix(raw: &str, site_name: &str) -> String {
+    let title = trim(raw);
+    if !site_name.is_empty()
+        && title.len() > site_name.len()
+        && let Some(rest) = title.strip_suffix(site_name)
+    {
+        let mut rest = trim(rest);
+        for sep in ["|", "-", "–", "—", "｜"] {
+            if let Some(stripped) = rest.strip_suffix(sep) {
+                rest = trim(stripped);
+                break;
+            }
+        }
+        if !rest.is_empty() {
+            return rest.to_string();
+        }
+    }
+
+    match title.rfind(" | ") {
+        Some(pos) if pos > 0 => trim(&title[..pos]).to_string(),
+        _ => title.to_string(),
+    }
+}
+
+fn find_sample_images(sample: ElementRef, base: &str) -> Vec<String> {
+    sample
+        .descendent_elements()
+        .filter(|el| is_tag(el, "img"))
+        .filter_map(|el| el.value().attr("src"))
+        .filter(|src| !src.is_empty())
+        .map(|src| resolve_url(base, src))
+        .collect()
+}
+
+// wall time at utc_offset (minutes) ---> epoch seconds
+fn parse_post_time(text: &str, utc_offset: i32) -> Option<i64> {
+    POST_TIME_RES.iter().find_map(|re| {
+        let c = re.captures(text)?;
+        let hour = c.get(4).map_or(Some(0), |h| h.as_str().parse().ok())?;
+        let minute = c.get(5).map_or(Some(0), |m| m.as_str().parse().ok())?;
+        let local =
+            NaiveDate::from_ymd_opt(c[1].parse().ok()?, c[2].parse().ok()?, c[3].parse().ok()?)?
+                .and_hms_opt(hour, minute, 0)?;
+
+        Some(local.and_utc().timestamp() - i64::from(utc_offset) * 60)
+    })
+}
+
+// 1709629200, +540 ---> "2024-03-05T18:00:00+09:00"
+fn to_iso8601(t: i64, utc_offset: i32) -> Option<String> {
+    let offset = FixedOffset::east_opt(utc_offset * 60)?;
+    Some(
+        DateTime::from_timestamp(t, 0)?
+            .with_timezone(&offset)
+            .to_rfc3339_opts(SecondsFormat::Secs, false),
+    )
+}
+
+#[derive(Default)]
+struct PostTags {
+    cv: Vec<Value>,
+    circle: Vec<Value>,
+    scenario: Vec<Value>,
+    illust: Vec<Value>,
+    genre: Vec<Value>,
+    series: Vec<Value>,
+}
+
+fn find_tags_from_post_div(post_tag: ElementRef) -> PostTags {
+    let mut tags = PostTags::default();
+    let mut title = String::new();
+
+    for el in post_tag.child_elements() {
+        if is_tag(&el, "span") {
+            title.clear();
+            inner_text(&el, &mut title);
+        } else if !title.is_empty() && is_tag(&el, "a") {
+            let Some(href) = el.value().attr("href") else {
+                continue;
+            };
+            let mut text = String::new();
+            inner_text(&el, &mut text);
+
+            let dest = match title.as_str() {
+                "声優" => &mut tags.cv,
+                "サークル" => &mut tags.circle,
+                "シナリオ" => &mut tags.scenario,
+                "イラスト" => &mut tags.illust,
+                "ジャンル" => &mut tags.genre,
+                "シリーズ" => &mut tags.series,
+                _ => continue,
+            };
+            dest.push(tag_json(text, href.to_string()));
+        }
+    }
+
+    tags
+}
+
+// "03:12" / "1:02:30" / "1時間2分30秒" / "62分" ---> seconds
+fn parse_duration(raw: &str) -> Option<i64> {
+    let text = trim(raw);
+    let num = |m: Option<regex::Match>| m.map_or(0, |m| m.as_str().parse::<i64>().unwrap());
+
+    if let Some(c) = CLOCK_RE.captures(text) {
+        let (h, m, s) = (num(c.get(1)), num(c.get(2)), num(c.get(3)));
+        if s >= 60 || (c.get(1).is_some() && m >= 60) {
+            return None;
+        }
+        return Some(h * 3600 + m * 60 + s);
+    }
+
+    if !text.is_empty()
+        && let Some(c) = DURATION_UNITS_RE.captures(text)
+    {
+        return Some(num(c.get(1)) * 3600 + num(c.get(2)) * 60 + num(c.get(3)));
+    }
+
+    None
+}
+
+struct TimeTable {
+    index: i32,
+    title: String,
+    time: String,
+    start: Option<i64>,
+    duration: Option<i64>,
+}
+
+impl TimeTable {
+    fn to_json(&self) -> Value {
+        json!({
+            "index": self.index,
+            "title": self.title,
+            "time": self.time,
+            "start": self.start,
+            "duration": self.duration,
+            "unparseable": self.start.is_none(),
+        })
+    }
+}
+
+fn find_table_from_chapter_div(chapter: ElementRef) -> Vec<TimeTable> {
+    let mut v = vec![];
+    let mut index = 0;
+
+    for link in chapter.child_elements().filter(|el| is_tag(el, "a")) {
+        let mut title = String::new();
+        let mut time = String::new();
+
+        for inner in link.children() {
+            if let Some(text) = inner.value().as_text() {
+                if !is_whitespace(text) {
+                    title = text.to_string();
+                }
+            } else if let Some(span) = ElementRef::wrap(inner).filter(|el| is_tag(el, "span")) {
+                inner_text(&span, &mut time);
+            }
+        }
+
+        if !title.is_empty() && !time.is_empty() {
+            v.push(TimeTable {
+                index,
+                title,
+                start: parse_duration(&time),
+                time,
+                duration: None,
+            });
+            index += 1;
+        }
+    }
+
+    v
+}
+
+// a chapter lasts until the next one starts, the last one until the end of the work (runtime)
+// missing or out of order neighbours leave the duration unknown
+fn fill_durations(v: &mut [TimeTable], runtime: Option<i64>) {
+    let ends: Vec<Option<i64>> = v
+        .iter()
+        .skip(1)
+        .map(|t| t.start)
+        .chain(std::iter::once(runtime))
+        .collect();
+
+    for (t, end) in v.iter_mut().zip(ends) {
+        if let (Some(start), Some(end)) = (t.start, end)
+            && end >= start
+        {
+            t.duration = Some(end - start);
+        }
+    }
+}
+
+pub fn find_meta(html: &str) -> Result<String, ParserErr> {
+    let document = Html::parse_document(html);
+    let mut walk = MetaWalk::default();
+    walk.walk(document.root_element());
+
+    // og fallbacks
+    let url = walk
+        .url
+        .filter(|url| !url.is_empty())
+        .or(walk.og_url)
+        .ok_or(ParseFailure::CanonicalUrlNotFound)?;
+    let title = match walk.title.filter(|title| !title.is_empty()) {
+        Some(title) => strip_site_suffix(&title, walk.og_site_name.as_deref().unwrap_or_default()),
+        None => trim(walk.og_title.as_deref().unwrap_or_default()).to_string(),
+    };
+    if title.is_empty() {
+        return Err(ParseFailure::TitleNotFound.into());
+    }
+    let utc_offset = META_UTC_OFFSET.load(Ordering::Relaxed);
+    let time_text = walk
+        .time
+        .filter(|t| !t.is_empty())
+        .ok_or(ParseFailure::PostTimeNotFound)?;
+    let time = parse_post_time(&time_text, utc_offset).ok_or_else(|| {
+        tracing::warn!("unparseable post-time: {time_text}");
+        ParseFailure::PostTimeUnparseable
+    })?;
+    let img = walk.img.filter(|img| !img.is_empty()).or(walk.og_image);
+    let description = walk
+        .description
+        .filter(|d| !d.is_empty())
+        .or(walk.og_description)
+        .map(|d| trim(&d).to_string())
+        .filter(|d| !d.is_empty());
+
+    let tags = walk
+        .post_tag
+        .map(find_tags_from_post_div)
+        .unwrap_or_default();
+    let runtime = walk.runtime.as_deref().and_then(parse_duration);
+    let mut time_tables = walk
+        .chapter
+        .map(find_table_from_chapter_div)
+        .unwrap_or_default();
+    fill_durations(&mut time_tables, runtime);
+    let sample_images = walk
+        .sample
+        .map(|sample| find_sample_images(sample, &url))
+        .unwrap_or_default();
+
+    let id = find_id_from_url(&url).ok_or(ParseFailure::WorkIdNotFound)?;
+
+    let j = json!({
+        "schema_version": META_SCHEMA_VERSION,
+        "id": id,
+        "title": title,
+        "url": url,
+        "img_src": img,
+        "time": time,
+        "time_iso": to_iso8601(time, utc_offset),
+        "description": description,
+        "price": walk.price.as_deref().and_then(parse_price),
+        "file_size": walk.file_size.filter(|size| !size.is_empty()),
+        "sample_images": sample_images,
+
+        "cv": tags.cv,
+        "genre": tags.genre,
+        "illust": tags.illust,
+        "circle": tags.circle,
+        "scenario": tags.scenario,
+        "series": tags.series,
+
+        "time_table": time_tables.iter().map(TimeTable::to_json).collect::<Vec<_>>(),
+        "runtime": runtime,
+    });
+
+    Ok(j.to_string())
+}
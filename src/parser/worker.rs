@@ -0,0 +1,273 @@
+// crash isolated parsing
+// the service re-executes itself as `<exe> parser-worker <name>` and talks to the child over pipes,
+// so a segfault or abort inside gumbo / nlohmann only takes the child down.
+//
+// frame: code (i32 le) | length (u32 le) | body
+//   request  : code 0, body = html
+//   response : code 0 -> body is the payload
+//              code > 0 -> ParseFailure code, body is the message
+//              code < 0 -> any other ParserErr, body is the message
+use std::{
+    io::{self, BufWriter, Read, Write},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    sync::{
+        Condvar, Mutex,
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    },
+    time::Duration,
+};
+
+use crate::parser::{ParseFailure, ParserErr};
+
+pub const PARSER_WORKER_ARG: &str = "parser-worker";
+
+type Frame = (i32, Vec<u8>);
+
+fn write_frame(w: &mut impl Write, code: i32, body: &[u8]) -> io::Result<()> {
+    w.write_all(&code.to_le_bytes())?;
+    w.write_all(&(body.len() as u32).to_le_bytes())?;
+    w.write_all(body)?;
+    w.flush()
+}
+
+fn read_frame(r: &mut impl Read) -> io::Result<Frame> {
+    let mut header = [0u8; 8];
+    r.read_exact(&mut header)?;
+    let code = i32::from_le_bytes(header[..4].try_into().unwrap());
+    let len = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+
+    let mut body = vec![0u8; len];
+    r.read_exact(&mut body)?;
+    Ok((code, body))
+}
+
+// entry point of the child process, returns when the parent closes stdin
+pub fn run_parser_worker(
+    parser: impl Fn(&str) -> Result<String, ParserErr>,
+) -> Result<(), io::Error> {
+    // anything written to fd 1 (by us or a linked library) would corrupt the frames,
+    // so answer on a private copy and point fd 1 at stderr
+    let mut proto = {
+        use std::os::fd::FromRawFd;
+        let fd = unsafe {
+            let fd = libc::dup(libc::STDOUT_FILENO);
+            if fd < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            fd
+        };
+        BufWriter::new(unsafe { std::fs::File::from_raw_fd(fd) })
+    };
+    let mut stdin = io::stdin().lock();
+
+    loop {
+        let (_, body) = match read_frame(&mut stdin) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let html = String::from_utf8_lossy(&body);
+        match parser(&html) {
+            Ok(payload) => write_frame(&mut proto, 0, payload.as_bytes())?,
+            Err(ParserErr::ParseFailure(failure)) => write_frame(
+                &mut proto,
+                failure.as_code(),
+                failure.to_string().as_bytes(),
+            )?,
+            Err(e) => write_frame(&mut proto, -1, e.to_string().as_bytes())?,
+        }
+    }
+}
+
+struct Worker {
+    child: Child,
+    requests: Sender<Vec<u8>>,
+    responses: Receiver<io::Result<Frame>>,
+}
+
+impl Worker {
+    fn spawn(mut command: Command) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+
+        let mut stdin = child.stdin.take().unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+
+        // a child that stops reading blocks any write past the pipe buffer,
+        // so writes get their own thread as well and the deadline covers them
+        let (request_tx, request_rx) = mpsc::channel::<Vec<u8>>();
+        std::thread::spawn(move || {
+            for html in request_rx {
+                if write_frame(&mut stdin, 0, &html).is_err() {
+                    break;
+                }
+            }
+        });
+
+        // blocking reads cannot time out, so they happen on their own thread
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            loop {
+                let frame = read_frame(&mut stdout);
+                let closed = frame.is_err();
+                if tx.send(frame).is_err() || closed {
+                    break;
+                }
+            }
+        });
+
+        Ok(Worker {
+            child,
+            requests: request_tx,
+            responses: rx,
+        })
+    }
+
+    fn crashed(&mut self) -> ParserErr {
+        let _ = self.child.kill();
+        match self.child.wait() {
+            Ok(status) => ParserErr::WorkerCrashed(format!("{status}")),
+            Err(e) => ParserErr::WorkerCrashed(format!("{e}")),
+        }
+    }
+
+    // Err(_) means the worker itself is gone and has to be replaced
+    fn parse(
+        &mut self,
+        html: &str,
+        timeout: Duration,
+    ) -> Result<Result<String, ParserErr>, ParserErr> {
+        // the writer is gone only when a write failed, i.e. the child did
+        if self.requests.send(html.as_bytes().to_vec()).is_err() {
+            return Err(self.crashed());
+        }
+
+        match self.responses.recv_timeout(timeout) {
+            Ok(Ok((0, body))) => Ok(Ok(String::from_utf8_lossy(&body).to_string())),
+            Ok(Ok((code, _))) if code > 0 => Ok(Err(ParseFailure::from_code(code).into())),
+            Ok(Ok((_, body))) => Ok(Err(ParserErr::WorkerErr(
+                String::from_utf8_lossy(&body).to_string(),
+            ))),
+            Ok(Err(_)) | Err(RecvTimeoutError::Disconnected) => Err(self.crashed()),
+            Err(RecvTimeoutError::Timeout) => Err(ParserErr::WorkerTimeout),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+pub struct WorkerPool {
+    parser_name: String,
+    // program and args of the child, None re-executes the service as
+    // `<exe> parser-worker <name>`
+    command: Option<(PathBuf, Vec<String>)>,
+    timeout: Duration,
+
+    // None is a slot whose worker has to be (re)spawned
+    slots: Mutex<Vec<Option<Worker>>>,
+    available: Condvar,
+}
+
+impl WorkerPool {
+    pub fn new(parser_name: &str, size: usize, timeout: Duration) -> Self {
+        WorkerPool {
+            parser_name: parser_name.to_string(),
+            command: None,
+            timeout,
+            slots: Mutex::new((0..size).map(|_| None).collect()),
+            available: Condvar::new(),
+        }
+    }
+
+    // any program speaking the frame protocol, e.g. a stub in tests
+    pub fn with_command(
+        parser_name: &str,
+        program: PathBuf,
+        args: Vec<String>,
+        size: usize,
+        timeout: Duration,
+    ) -> Self {
+        WorkerPool {
+            command: Some((program, args)),
+            ..WorkerPool::new(parser_name, size, timeout)
+        }
+    }
+
+    fn spawn(&self) -> io::Result<Worker> {
+        let command = match &self.command {
+            Some((program, args)) => {
+                let mut command = Command::new(program);
+                command.args(args);
+                command
+            }
+            None => {
+                let mut command = Command::new(std::env::current_exe()?);
+                command.arg(PARSER_WORKER_ARG).arg(&self.parser_name);
+                command
+            }
+        };
+        Worker::spawn(command)
+    }
+
+    fn checkout(&self) -> Option<Worker> {
+        let mut slots = self.slots.lock().unwrap();
+        loop {
+            if let Some(slot) = slots.pop() {
+                return slot;
+            }
+            slots = self.available.wait(slots).unwrap();
+        }
+    }
+
+    fn checkin(&self, worker: Option<Worker>) {
+        self.slots.lock().unwrap().push(worker);
+        self.available.notify_one();
+    }
+
+    fn respawn(&self) -> Option<Worker> {
+        match self.spawn() {
+            Ok(worker) => Some(worker),
+            Err(e) => {
+                tracing::error!("failed to spawn parser worker {}: {e}", self.parser_name);
+                None
+            }
+        }
+    }
+
+    // blocking, meant to be called from the parse pool
+    pub fn parse(&self, html: &str) -> Result<String, ParserErr> {
+        let mut worker = match self.checkout() {
+            Some(worker) => worker,
+            None => match self.spawn() {
+                Ok(worker) => worker,
+                Err(e) => {
+                    self.checkin(None);
+                    return Err(e.into());
+                }
+            },
+        };
+
+        match worker.parse(html, self.timeout) {
+            Ok(result) => {
+                self.checkin(Some(worker));
+                result
+            }
+            Err(e) => {
+                tracing::error!("parser worker {} replaced: {e}", self.parser_name);
+                drop(worker);
+                self.checkin(self.respawn());
+                Err(e)
+            }
+        }
+    }
+}
@@ -6,6 +6,10 @@ pub trait RedisRequest {
     fn get_job_id(&self) -> String;
     fn index(&self) -> i32;
     fn is_forced(&self) -> bool;
+    // keep one proxy for every attempt, None leaves it to the path (PROXY_STICKY)
+    fn sticky_proxy(&self) -> Option<bool> {
+        None
+    }
 }
 
 #[derive(serde::Deserialize)]
@@ -15,13 +19,57 @@ pub struct BasicRedisReq {
     job_id: String,
     index: i32,
     force: Option<bool>,
+    sticky_proxy: Option<bool>,
 }
 
 #[derive(Serialize)]
 pub struct RedisResponse {
     pub error: Option<String>,
+    // stable, machine readable counterpart of `error`
+    pub error_code: Option<String>,
     pub payload: Option<String>,
     pub index: i32,
+    // only for fetched items
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<ResponseMeta>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyOutcome {
+    Ok,
+    // 404, 410, ... the proxy did its job
+    PermanentStatus,
+    RetryableStatus,
+    TransportError,
+}
+
+impl ProxyOutcome {
+    pub fn is_healthy(&self) -> bool {
+        matches!(self, ProxyOutcome::Ok | ProxyOutcome::PermanentStatus)
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ProxyAttempt {
+    // without credentials
+    pub proxy: String,
+    pub outcome: ProxyOutcome,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ResponseMeta {
+    pub attempts: i32,
+    // of the last response, none when nothing came back
+    pub status: Option<u16>,
+    // one per attempt that went out through a proxy
+    pub proxies: Vec<ProxyAttempt>,
+    // 304, the payload is the one of the previous fetch
+    pub not_modified: bool,
+    // the body was decoded from, e.g. "Shift_JIS"
+    pub charset: Option<String>,
+    // malformed bytes were replaced with U+FFFD
+    pub charset_errors: bool,
 }
 
 impl RedisRequest for BasicRedisReq {
@@ -42,4 +90,8 @@ impl RedisRequest for BasicRedisReq {
     fn is_forced(&self) -> bool {
         self.force.unwrap_or(false)
     }
+
+    fn sticky_proxy(&self) -> Option<bool> {
+        self.sticky_proxy
+    }
 }
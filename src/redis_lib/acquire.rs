@@ -18,6 +18,8 @@ pub enum AcquireErr {
     OverRetry,
 }
 
+// only implemented inside this crate, the futures are awaited in place
+#[allow(async_fn_in_trait)]
 pub trait AcquireConfigTrait<Src, E>: Send {
     type Output<'a>
     where
@@ -1,4 +1,5 @@
 mod acquire;
+mod rate_limit;
 mod req_fetch;
 
 use bb8::PooledConnection;
@@ -7,6 +8,7 @@ use redis::{AsyncCommands, RedisError};
 use std::string::FromUtf8Error;
 
 pub use acquire::{AcquireConfigTrait, ClientAcquireConfig, PoolAcquireConfig};
+pub use rate_limit::reserve_rate_limit;
 pub use req_fetch::{ReqFetchContract, RequestFetcherErr, invoke_req_fetcher};
 
 #[derive(thiserror::Error, Debug)]
@@ -59,3 +61,111 @@ pub async fn push_result(
 ) -> Result<(), RedisLibErr> {
     Ok(conn.hset(keyword, task_id, result).await?)
 }
+
+pub async fn get_robots_txt(
+    key: &str,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<Option<String>, RedisLibErr> {
+    Ok(conn.get::<&str, Option<String>>(key).await?)
+}
+
+// an empty body stands for a missing robots.txt
+pub async fn cache_robots_txt(
+    key: &str,
+    body: &str,
+    ttl: usize,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<(), RedisLibErr> {
+    Ok(redis::cmd("SET")
+        .arg(key)
+        .arg(body)
+        .arg("EX")
+        .arg(ttl)
+        .query_async(&mut **conn)
+        .await?)
+}
+
+pub async fn get_validators(
+    key: &str,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<Option<String>, RedisLibErr> {
+    Ok(conn.get::<&str, Option<String>>(key).await?)
+}
+
+// overwritten on every 200, i.e. the ttl restarts
+pub async fn store_validators(
+    key: &str,
+    validators: &str,
+    ttl: usize,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<(), RedisLibErr> {
+    Ok(redis::cmd("SET")
+        .arg(key)
+        .arg(validators)
+        .arg("EX")
+        .arg(ttl)
+        .query_async(&mut **conn)
+        .await?)
+}
+
+// on a 304, the ttl keeps counting from the 200 that produced the payload
+// XX: an expired key is not brought back without a ttl
+pub async fn refresh_validators(
+    key: &str,
+    validators: &str,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<(), RedisLibErr> {
+    Ok(redis::cmd("SET")
+        .arg(key)
+        .arg(validators)
+        .arg("XX")
+        .arg("KEEPTTL")
+        .query_async(&mut **conn)
+        .await?)
+}
+
+pub async fn store_archive(
+    key: &str,
+    record: &[u8],
+    ttl: usize,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<(), RedisLibErr> {
+    Ok(redis::cmd("SET")
+        .arg(key)
+        .arg(record)
+        .arg("EX")
+        .arg(ttl)
+        .query_async(&mut **conn)
+        .await?)
+}
+
+pub async fn get_archive(
+    key: &str,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<Option<Vec<u8>>, RedisLibErr> {
+    Ok(conn.get::<&str, Option<Vec<u8>>>(key).await?)
+}
+
+// SCAN, not KEYS, the archive can be large
+pub async fn scan_keys(
+    pattern: &str,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<Vec<String>, RedisLibErr> {
+    let mut keys = vec![];
+    let mut cursor = 0u64;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+            .arg(cursor)
+            .arg("MATCH")
+            .arg(pattern)
+            .arg("COUNT")
+            .arg(1000)
+            .query_async(&mut **conn)
+            .await?;
+        keys.extend(batch);
+        if next == 0 {
+            return Ok(keys);
+        }
+        cursor = next;
+    }
+}
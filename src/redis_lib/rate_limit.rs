@@ -0,0 +1,52 @@
+use std::{sync::LazyLock, time::Duration};
+
+use bb8::PooledConnection;
+use bb8_redis::RedisConnectionManager;
+use redis::Script;
+
+use crate::redis_lib::RedisLibErr;
+
+// GCRA, KEYS[1] holds the theoretical arrival time (us) of the next request to a host
+// the slot is always taken, the reply is how long to wait for it (us),
+// so callers on every replica queue up behind each other
+// redis TIME is used as the clock, replicas do not have to agree on theirs
+static GCRA: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000000 + tonumber(time[2])
+local interval = tonumber(ARGV[1])
+local burst = tonumber(ARGV[2])
+
+local tat = tonumber(redis.call('GET', KEYS[1]) or now)
+if tat < now then
+    tat = now
+end
+local new_tat = tat + interval
+local wait = new_tat - interval * burst - now
+if wait < 0 then
+    wait = 0
+end
+
+redis.call('SET', KEYS[1], string.format('%d', new_tat), 'PX', math.ceil((new_tat - now) / 1000) + 1)
+return wait
+",
+    )
+});
+
+// interval is 1 / rps, burst requests go through at once after an idle period
+pub async fn reserve_rate_limit(
+    key: &str,
+    interval: Duration,
+    burst: u32,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> Result<Duration, RedisLibErr> {
+    let wait_micros: u64 = GCRA
+        .key(key)
+        .arg(interval.as_micros() as u64)
+        .arg(burst.max(1))
+        .invoke_async(&mut **conn)
+        .await?;
+
+    Ok(Duration::from_micros(wait_micros))
+}
@@ -0,0 +1,352 @@
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use bb8::{Pool, RunError};
+use bb8_redis::RedisConnectionManager;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::redis_lib::{RedisLibErr, get_archive, scan_keys, store_archive};
+
+#[derive(thiserror::Error, Debug)]
+pub enum ArchiveErr {
+    #[error("{0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    SerdeJson(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    RunErr(#[from] RunError<redis::RedisError>),
+
+    #[error("{0}")]
+    RedisLib(#[from] RedisLibErr),
+
+    #[error("{0}")]
+    PruneTaskErr(#[from] tokio::task::JoinError),
+
+    #[error("malformed archive record")]
+    Malformed,
+}
+
+// everything but the body, kept as the first line of the record
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ArchiveHeader {
+    // e.g. "meta"
+    pub path: String,
+    // of the request, replay writes the result back under it
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub index: i32,
+    pub identifier: String,
+    pub url: String,
+    // unix millis
+    pub fetched_at: u64,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ArchiveRecord {
+    pub header: ArchiveHeader,
+    // as received, before any decoding
+    pub body: Vec<u8>,
+}
+
+pub fn unix_millis(at: SystemTime) -> u64 {
+    at.duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_millis() as u64
+}
+
+pub fn header_pairs(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                String::from_utf8_lossy(value.as_bytes()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+// back from header_pairs, invalid pairs are dropped
+pub fn header_map(pairs: &[(String, String)]) -> HeaderMap {
+    pairs
+        .iter()
+        .filter_map(|(name, value)| {
+            Some((
+                HeaderName::from_bytes(name.as_bytes()).ok()?,
+                HeaderValue::from_str(value).ok()?,
+            ))
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArchiveLocation {
+    File(PathBuf),
+    Key(String),
+}
+
+// what the name of a record tells without reading it
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveEntry {
+    pub path: String,
+    pub identifier: String,
+    pub fetched_at: u64,
+    pub location: ArchiveLocation,
+}
+
+impl ArchiveRecord {
+    // "<identifier>-<fetched_at>", unique enough per path
+    pub fn name(&self) -> String {
+        format!("{}-{}", self.header.identifier, self.header.fetched_at)
+    }
+
+    // zstd(header json + "\n" + body), compact json has no raw newline
+    pub fn encode(&self, level: i32) -> Result<Vec<u8>, ArchiveErr> {
+        let mut encoder = zstd::Encoder::new(vec![], level)?;
+        serde_json::to_writer(&mut encoder, &self.header)?;
+        encoder.write_all(b"\n")?;
+        encoder.write_all(&self.body)?;
+
+        Ok(encoder.finish()?)
+    }
+
+    pub fn decode(compressed: &[u8]) -> Result<Self, ArchiveErr> {
+        let mut raw = zstd::decode_all(compressed)?;
+        let split = raw
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or(ArchiveErr::Malformed)?;
+        let header = serde_json::from_slice(&raw[..split])?;
+        let body = raw.split_off(split + 1);
+
+        Ok(ArchiveRecord { header, body })
+    }
+}
+
+pub enum ArchiveStore {
+    // <dir>/<path>/<identifier>-<fetched_at>.zst
+    Disk {
+        dir: PathBuf,
+    },
+    // <key_prefix>:<path>:<identifier>:<fetched_at>, expiring with the retention
+    Redis {
+        pool: Arc<Pool<RedisConnectionManager>>,
+        key_prefix: String,
+    },
+}
+
+// opt-in, every fetched body goes here before parsing
+pub struct ArchiveSink {
+    pub store: ArchiveStore,
+    pub retention: Duration,
+    // zstd level, 1 ..= 22
+    pub level: i32,
+}
+
+impl ArchiveSink {
+    pub async fn write(&self, record: &ArchiveRecord) -> Result<(), ArchiveErr> {
+        let compressed = record.encode(self.level)?;
+        match &self.store {
+            ArchiveStore::Disk { dir } => {
+                let dir = dir.join(&record.header.path);
+                tokio::fs::create_dir_all(&dir).await?;
+                tokio::fs::write(dir.join(format!("{}.zst", record.name())), compressed).await?;
+            }
+            ArchiveStore::Redis { pool, key_prefix } => {
+                let key = format!(
+                    "{key_prefix}:{}:{}:{}",
+                    record.header.path, record.header.identifier, record.header.fetched_at
+                );
+                let mut conn = pool.get().await?;
+                store_archive(
+                    &key,
+                    &compressed,
+                    self.retention.as_secs() as usize,
+                    &mut conn,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // every record of these paths, of all paths when empty
+    pub async fn list(&self, paths: &[String]) -> Result<Vec<ArchiveEntry>, ArchiveErr> {
+        match &self.store {
+            ArchiveStore::Disk { dir } => {
+                let (dir, paths) = (dir.clone(), paths.to_vec());
+                tokio::task::spawn_blocking(move || list_dir(&dir, &paths)).await?
+            }
+            ArchiveStore::Redis { pool, key_prefix } => {
+                let patterns = if paths.is_empty() {
+                    vec![format!("{key_prefix}:*")]
+                } else {
+                    paths
+                        .iter()
+                        .map(|path| format!("{key_prefix}:{path}:*"))
+                        .collect()
+                };
+                let mut conn = pool.get().await?;
+                let mut entries = vec![];
+                for pattern in patterns {
+                    for key in scan_keys(&pattern, &mut conn).await? {
+                        if let Some(entry) = entry_of_key(key_prefix, key) {
+                            entries.push(entry);
+                        }
+                    }
+                }
+                Ok(entries)
+            }
+        }
+    }
+
+    // None when it expired since it was listed
+    pub async fn read(&self, entry: &ArchiveEntry) -> Result<Option<ArchiveRecord>, ArchiveErr> {
+        let compressed = match (&self.store, &entry.location) {
+            (_, ArchiveLocation::File(file)) => match tokio::fs::read(file).await {
+                Ok(compressed) => compressed,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                Err(e) => return Err(e.into()),
+            },
+            (ArchiveStore::Redis { pool, .. }, ArchiveLocation::Key(key)) => {
+                let mut conn = pool.get().await?;
+                match get_archive(key, &mut conn).await? {
+                    Some(compressed) => compressed,
+                    None => return Ok(None),
+                }
+            }
+            (ArchiveStore::Disk { .. }, ArchiveLocation::Key(_)) => return Ok(None),
+        };
+
+        Ok(Some(ArchiveRecord::decode(&compressed)?))
+    }
+
+    // disk only, redis keys expire on their own
+    pub async fn prune(&self, now: SystemTime) -> Result<usize, ArchiveErr> {
+        let ArchiveStore::Disk { dir } = &self.store else {
+            return Ok(0);
+        };
+        let dir = dir.clone();
+        let oldest = unix_millis(now).saturating_sub(self.retention.as_millis() as u64);
+
+        tokio::task::spawn_blocking(move || prune_dir(&dir, oldest)).await?
+    }
+}
+
+// "<identifier>-<fetched_at>.zst"
+fn name_of(file: &std::path::Path) -> Option<(String, u64)> {
+    let stem = file.file_stem()?.to_str()?;
+    let (identifier, fetched_at) = stem.rsplit_once('-')?;
+
+    Some((identifier.to_string(), fetched_at.parse().ok()?))
+}
+
+fn fetched_at_of(file: &std::path::Path) -> Option<u64> {
+    name_of(file).map(|(_, fetched_at)| fetched_at)
+}
+
+// "<key_prefix>:<path>:<identifier>:<fetched_at>"
+fn entry_of_key(key_prefix: &str, key: String) -> Option<ArchiveEntry> {
+    let rest = key.strip_prefix(key_prefix)?.strip_prefix(':')?;
+    let mut parts = rest.rsplitn(3, ':');
+    let fetched_at = parts.next()?.parse().ok()?;
+    let identifier = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    Some(ArchiveEntry {
+        path,
+        identifier,
+        fetched_at,
+        location: ArchiveLocation::Key(key),
+    })
+}
+
+fn list_dir(dir: &std::path::Path, paths: &[String]) -> Result<Vec<ArchiveEntry>, ArchiveErr> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut entries = vec![];
+    for path_dir in std::fs::read_dir(dir)? {
+        let path_dir = path_dir?.path();
+        let Some(path) = path_dir.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !path_dir.is_dir() || !(paths.is_empty() || paths.iter().any(|p| p == path)) {
+            continue;
+        }
+        for file in std::fs::read_dir(&path_dir)? {
+            let file = file?.path();
+            if let Some((identifier, fetched_at)) = name_of(&file) {
+                entries.push(ArchiveEntry {
+                    path: path.to_string(),
+                    identifier,
+                    fetched_at,
+                    location: ArchiveLocation::File(file),
+                });
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn prune_dir(dir: &std::path::Path, oldest: u64) -> Result<usize, ArchiveErr> {
+    if !dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for path_dir in std::fs::read_dir(dir)? {
+        let path_dir = path_dir?.path();
+        if !path_dir.is_dir() {
+            continue;
+        }
+        for file in std::fs::read_dir(&path_dir)? {
+            let file = file?.path();
+            if fetched_at_of(&file).is_some_and(|fetched_at| fetched_at < oldest) {
+                std::fs::remove_file(&file)?;
+                removed += 1;
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+pub fn invoke_archive_pruner(
+    set: &mut JoinSet<()>,
+    token: CancellationToken,
+    sink: Arc<ArchiveSink>,
+    interval: Duration,
+) {
+    set.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    match sink.prune(SystemTime::now()).await {
+                        Ok(removed) => tracing::info!(target: "metrics", removed, "archive pruned"),
+                        Err(e) => tracing::error!("{e}"),
+                    }
+                },
+                _ = token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    });
+}
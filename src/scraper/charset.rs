@@ -0,0 +1,79 @@
+use std::sync::LazyLock;
+
+use encoding_rs::Encoding;
+use regex::bytes::Regex;
+use reqwest::header::{CONTENT_TYPE, HeaderMap};
+
+// html spec prescan, a meta past this is not looked for
+const META_PRESCAN: usize = 1024;
+
+static META_CHARSET: LazyLock<Regex> = LazyLock::new(|| {
+    // <meta charset="..."> and <meta http-equiv="Content-Type" content="...; charset=...">
+    Regex::new(r#"(?i)<meta\s[^>]*?charset\s*=\s*["']?\s*([a-z0-9_:.\-]+)"#).unwrap()
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CharsetSource {
+    Bom,
+    Header,
+    Meta,
+    Fallback,
+}
+
+// "shift_jis", "EUC-JP", "utf-8", ... as in the encoding standard
+pub fn parse_encoding(label: &str) -> Option<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+fn header_charset(headers: &HeaderMap) -> Option<&'static Encoding> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    content_type.split(';').skip(1).find_map(|param| {
+        let (key, label) = param.split_once('=')?;
+        if !key.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        parse_encoding(label.trim().trim_matches(|c| c == '"' || c == '\''))
+    })
+}
+
+fn meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    let head = &body[..body.len().min(META_PRESCAN)];
+    let label = META_CHARSET.captures(head)?.get(1)?.as_bytes();
+    // a document whose meta could be read as ascii is not utf-16, i.e. utf-8
+    Some(Encoding::for_label(label)?.output_encoding())
+}
+
+// bom, Content-Type, <meta>, then the fallback, the order browsers use
+pub fn sniff_charset(
+    headers: &HeaderMap,
+    body: &[u8],
+    fallback: &'static Encoding,
+) -> (&'static Encoding, CharsetSource) {
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return (encoding, CharsetSource::Bom);
+    }
+    if let Some(encoding) = header_charset(headers) {
+        return (encoding, CharsetSource::Header);
+    }
+    if let Some(encoding) = meta_charset(body) {
+        return (encoding, CharsetSource::Meta);
+    }
+
+    (fallback, CharsetSource::Fallback)
+}
+
+// utf-8 for the parsers, malformed bytes become U+FFFD rather than failing
+// the item, the flag is reported next to the charset (ResponseMeta)
+pub fn decode_body(
+    headers: &HeaderMap,
+    body: &[u8],
+    fallback: &'static Encoding,
+) -> (String, &'static Encoding, bool) {
+    let (encoding, source) = sniff_charset(headers, body, fallback);
+    let (text, used, had_errors) = encoding.decode(body);
+    if had_errors {
+        tracing::warn!(encoding = used.name(), ?source, "malformed body");
+    }
+
+    (text.into_owned(), used, had_errors)
+}
@@ -0,0 +1,128 @@
+use bb8::PooledConnection;
+use bb8_redis::RedisConnectionManager;
+use reqwest::{
+    RequestBuilder,
+    header::{ETAG, HeaderMap, HeaderName, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::redis_lib::{RedisLibErr, get_validators, refresh_validators, store_validators};
+
+fn header_value(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+// what the last 200 of an identifier left behind, the payload answers a 304
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub payload: String,
+}
+
+impl Validators {
+    // None when the site sends neither header, nothing to revalidate with
+    pub fn from_headers(headers: &HeaderMap, payload: String) -> Option<Self> {
+        let (etag, last_modified) = (
+            header_value(headers, ETAG),
+            header_value(headers, LAST_MODIFIED),
+        );
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+
+        Some(Validators {
+            etag,
+            last_modified,
+            payload,
+        })
+    }
+
+    // a 304 may carry newer validators, the payload stays
+    pub fn revalidated(&self, headers: &HeaderMap) -> Self {
+        Validators {
+            etag: header_value(headers, ETAG).or_else(|| self.etag.clone()),
+            last_modified: header_value(headers, LAST_MODIFIED)
+                .or_else(|| self.last_modified.clone()),
+            payload: self.payload.clone(),
+        }
+    }
+
+    pub fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = match &self.etag {
+            Some(etag) => request.header(IF_NONE_MATCH, etag),
+            None => request,
+        };
+        match &self.last_modified {
+            Some(last_modified) => request.header(IF_MODIFIED_SINCE, last_modified),
+            None => request,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConditionalErr {
+    #[error("{0}")]
+    RedisLib(#[from] RedisLibErr),
+
+    #[error("{0}")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+// opt-in, validators are loaded in the prior stage and stored in the post stage
+pub struct ConditionalGet {
+    pub key_prefix: String,
+    // seconds, should outlive STORAGE_TIME so recrawls still find them
+    pub ttl: usize,
+}
+
+impl ConditionalGet {
+    fn key(&self, identifier: &str) -> String {
+        format!("{}:{identifier}", self.key_prefix)
+    }
+
+    pub async fn load(
+        &self,
+        identifier: &str,
+        conn: &mut PooledConnection<'_, RedisConnectionManager>,
+    ) -> Result<Option<Validators>, ConditionalErr> {
+        match get_validators(&self.key(identifier), conn).await? {
+            Some(stored) => Ok(Some(serde_json::from_str(&stored)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn store(
+        &self,
+        identifier: &str,
+        validators: &Validators,
+        conn: &mut PooledConnection<'_, RedisConnectionManager>,
+    ) -> Result<(), ConditionalErr> {
+        Ok(store_validators(
+            &self.key(identifier),
+            &serde_json::to_string(validators)?,
+            self.ttl,
+            conn,
+        )
+        .await?)
+    }
+
+    // stale payloads are not kept alive by 304s, parser fixes reach the page
+    // once the validators of its last 200 expire
+    pub async fn refresh(
+        &self,
+        identifier: &str,
+        validators: &Validators,
+        conn: &mut PooledConnection<'_, RedisConnectionManager>,
+    ) -> Result<(), ConditionalErr> {
+        Ok(refresh_validators(
+            &self.key(identifier),
+            &serde_json::to_string(validators)?,
+            conn,
+        )
+        .await?)
+    }
+}
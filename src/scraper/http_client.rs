@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use reqwest::header::{
+    ACCEPT_LANGUAGE, HeaderMap, HeaderName, HeaderValue, InvalidHeaderName, InvalidHeaderValue,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum HttpClientErr {
+    #[error("{0}")]
+    InvalidHeaderName(#[from] InvalidHeaderName),
+
+    #[error("{0}")]
+    InvalidHeaderValue(#[from] InvalidHeaderValue),
+
+    #[error("{0}")]
+    ReqwestErr(#[from] reqwest::Error),
+}
+
+// one reqwest::Client is built per path from this
+#[derive(Clone, Debug)]
+pub struct HttpClientConfig {
+    pub connect_timeout: Option<Duration>,
+    // between two reads of the body, a stalled origin fails here
+    pub read_timeout: Option<Duration>,
+    // whole request, body included
+    pub total_timeout: Option<Duration>,
+
+    pub user_agent: Option<String>,
+    pub accept_language: Option<String>,
+    pub default_headers: Vec<(String, String)>,
+
+    pub gzip: bool,
+    pub brotli: bool,
+    // 0 does not follow redirects
+    pub max_redirects: usize,
+}
+
+impl Default for HttpClientConfig {
+    fn default() -> Self {
+        HttpClientConfig {
+            connect_timeout: None,
+            read_timeout: None,
+            total_timeout: None,
+            user_agent: None,
+            accept_language: None,
+            default_headers: vec![],
+            gzip: true,
+            brotli: true,
+            max_redirects: 10,
+        }
+    }
+}
+
+impl HttpClientConfig {
+    pub fn build(&self) -> Result<reqwest::Client, HttpClientErr> {
+        self.build_with_proxy(None)
+    }
+
+    // one client per egress proxy, see ProxyPool
+    pub fn build_with_proxy(&self, proxy: Option<&str>) -> Result<reqwest::Client, HttpClientErr> {
+        let mut headers = HeaderMap::new();
+        if let Some(accept_language) = &self.accept_language {
+            headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_str(accept_language)?);
+        }
+        for (name, value) in &self.default_headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .default_headers(headers)
+            .gzip(self.gzip)
+            .brotli(self.brotli)
+            .redirect(match self.max_redirects {
+                0 => reqwest::redirect::Policy::none(),
+                max => reqwest::redirect::Policy::limited(max),
+            });
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.read_timeout {
+            builder = builder.read_timeout(timeout);
+        }
+        if let Some(timeout) = self.total_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(proxy) = proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+// "Name: value|Name: value", as written in HTTP_HEADERS
+pub fn parse_header_list(s: &str) -> Option<Vec<(String, String)>> {
+    s.split('|')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(|header| {
+            let (name, value) = header.split_once(':')?;
+            let name = name.trim();
+            (!name.is_empty()).then(|| (name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
@@ -1,9 +1,37 @@
-use std::{pin::Pin, sync::Arc};
-use tokio_util::io::simplex::new;
+mod archive;
+mod charset;
+mod conditional;
+mod http_client;
+mod parse_pool;
+mod proxy;
+mod rate_limit;
+mod retry;
+mod robots;
 
-use crate::{parser::ParserErr, redis_communication::RedisResponse};
+use encoding_rs::Encoding;
+use reqwest::{StatusCode, header::HeaderMap};
+use std::{pin::Pin, sync::Arc, time::SystemTime};
 
-type Scraper<Output> = dyn Fn(reqwest::Client, String) -> Pin<Box<dyn Future<Output = Output> + Send + Sync + 'static>>
+use crate::{
+    parser::ParserErr,
+    redis_communication::{ProxyAttempt, ProxyOutcome, ResponseMeta},
+    serv_engine::create_identifier,
+};
+
+pub use archive::{
+    ArchiveEntry, ArchiveErr, ArchiveHeader, ArchiveLocation, ArchiveRecord, ArchiveSink,
+    ArchiveStore, header_map, header_pairs, invoke_archive_pruner, unix_millis,
+};
+pub use charset::{CharsetSource, decode_body, parse_encoding, sniff_charset};
+pub use conditional::{ConditionalErr, ConditionalGet, Validators};
+pub use http_client::{HttpClientConfig, HttpClientErr, parse_header_list};
+pub use parse_pool::{ParsePool, ParsePoolMetrics, invoke_parse_pool_reporter};
+pub use proxy::{ProxyClients, ProxyPool, ProxySelection};
+pub use rate_limit::{ClusterRateLimiter, HostRateLimiter, RateLimiter, host_of};
+pub use retry::{RetryPolicy, StatusClass, classify, parse_retry_after};
+pub use robots::{RobotsErr, RobotsPolicy, RobotsRules};
+
+type Scraper<Output> = dyn Fn(FetchContext, String) -> Pin<Box<dyn Future<Output = Output> + Send + Sync + 'static>>
     + Send
     + Sync
     + 'static;
@@ -16,35 +44,248 @@ pub enum ScrapeErr {
     #[error("{0}")]
     ReqwestErr(#[from] reqwest::Error),
 
+    #[error("{0}")]
+    ParseTaskErr(#[from] tokio::task::JoinError),
+
     #[error("")]
     OverRetry,
+
+    // final status, after the retries when it was 429 / 5xx
+    #[error("http status {status} after {attempts} attempt(s)")]
+    HttpStatus {
+        status: reqwest::StatusCode,
+        attempts: i32,
+    },
+}
+
+// what the scrape stage hands to the scraper for each item
+#[derive(Clone)]
+pub struct FetchContext {
+    // direct, when the path has no proxies
+    pub client: reqwest::Client,
+    pub proxies: Option<Arc<ProxyClients>>,
+    // one proxy for every attempt of the request, unless it gets ejected
+    pub sticky_proxy: bool,
+    // of the previous fetch, sent as If-None-Match / If-Modified-Since
+    pub validators: Option<Validators>,
+    // e.g. "meta", names the archived records
+    pub path_name: String,
+    // of the item, kept in the archived record for replay
+    pub request_id: String,
+    pub index: i32,
+    pub archive: Option<Arc<ArchiveSink>>,
+    // for bodies without bom, charset header or <meta charset>
+    pub fallback_encoding: &'static Encoding,
+}
+
+pub struct Scraped {
+    pub result: Result<String, ScrapeErr>,
+    pub meta: ResponseMeta,
+    // to store for the next fetch
+    pub validators: Option<Validators>,
+}
+
+impl ScrapeErr {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ScrapeErr::ParserErr(e) => e.code(),
+            ScrapeErr::ReqwestErr(_) => "request_failed",
+            ScrapeErr::ParseTaskErr(_) => "parser_panicked",
+            ScrapeErr::OverRetry => "over_retry",
+            ScrapeErr::HttpStatus { status, .. } => match classify(*status) {
+                StatusClass::Retryable => "http_retry_exhausted",
+                _ => "http_permanent_status",
+            },
+        }
+    }
+}
+
+fn record_proxy(
+    ctx: &FetchContext,
+    proxy: Option<usize>,
+    outcome: ProxyOutcome,
+    meta: &mut ResponseMeta,
+) {
+    if let (Some(proxies), Some(idx)) = (&ctx.proxies, proxy) {
+        proxies.pool.report(idx, outcome.is_healthy());
+        meta.proxies.push(ProxyAttempt {
+            proxy: proxies.pool.label(idx).to_string(),
+            outcome,
+        });
+    }
+}
+
+// off the fetch path, a failed write only costs the record
+fn archive_response(
+    ctx: &FetchContext,
+    url: &str,
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &[u8],
+) {
+    let Some(archive) = ctx.archive.clone() else {
+        return;
+    };
+    let record = ArchiveRecord {
+        header: ArchiveHeader {
+            path: ctx.path_name.clone(),
+            id: ctx.request_id.clone(),
+            index: ctx.index,
+            identifier: create_identifier(url),
+            url: url.to_string(),
+            fetched_at: unix_millis(SystemTime::now()),
+            status: status.as_u16(),
+            headers: header_pairs(headers),
+        },
+        body: body.to_vec(),
+    };
+    tokio::spawn(async move {
+        if let Err(e) = archive.write(&record).await {
+            tracing::error!(url = %record.header.url, "archive: {e}");
+        }
+    });
 }
 
 pub fn generate_scraper(
     parser: impl Fn(&str) -> Result<String, ParserErr> + Send + Sync + 'static,
-    retry: i32,
-) -> Arc<Scraper<Result<String, ScrapeErr>>> {
+    retry: RetryPolicy,
+    parse_pool: Arc<ParsePool>,
+    rate_limiter: Arc<RateLimiter>,
+) -> Arc<Scraper<Scraped>> {
     let parser = Arc::new(parser);
+    let retry = Arc::new(retry);
 
-    Arc::new(move |client: reqwest::Client, url: String| {
+    Arc::new(move |ctx: FetchContext, url: String| {
         let moved_parser = parser.clone();
+        let moved_parse_pool = parse_pool.clone();
+        let moved_retry = retry.clone();
+        let moved_rate_limiter = rate_limiter.clone();
 
         Box::pin(async move {
-            let mut tempt = 0;
-            while tempt < retry {
-                let req = client.get(&url);
-                match req.send().await {
-                    Ok(result) => {
-                        let text = result.text().await.unwrap();
-                        return Ok(moved_parser(&text)?);
+            let host = host_of(&url);
+            let mut meta = ResponseMeta::default();
+            let mut sticky = None;
+            loop {
+                meta.attempts += 1;
+                let attempt = meta.attempts;
+                // every attempt is a request to the site, retries included
+                moved_rate_limiter.acquire(&host).await;
+
+                let proxy = ctx.proxies.as_ref().map(|proxies| match sticky {
+                    Some(idx) if proxies.pool.is_available(idx) => idx,
+                    _ => proxies.pool.pick(),
+                });
+                if ctx.sticky_proxy {
+                    sticky = proxy;
+                }
+                let client = match (&ctx.proxies, proxy) {
+                    (Some(proxies), Some(idx)) => &proxies.clients[idx],
+                    _ => &ctx.client,
+                };
+
+                let request = match &ctx.validators {
+                    Some(validators) => validators.apply(client.get(&url)),
+                    None => client.get(&url),
+                };
+
+                // (Retry-After, error to return once the retries are over)
+                let (retry_after, last_err, outcome) = match request.send().await {
+                    Ok(response) => {
+                        let status = response.status();
+                        meta.status = Some(status.as_u16());
+                        // unchanged, no body to parse
+                        if status == StatusCode::NOT_MODIFIED
+                            && let Some(validators) = &ctx.validators
+                        {
+                            record_proxy(&ctx, proxy, ProxyOutcome::Ok, &mut meta);
+                            meta.not_modified = true;
+                            return Scraped {
+                                result: Ok(validators.payload.clone()),
+                                meta,
+                                validators: Some(validators.revalidated(response.headers())),
+                            };
+                        }
+                        let headers = response.headers().clone();
+                        match response.bytes().await {
+                            Ok(body) => {
+                                // error pages too, soft 404s and block pages are what a
+                                // wrong looking parse output is checked against
+                                archive_response(&ctx, &url, status, &headers, &body);
+                                match classify(status) {
+                                    StatusClass::Success => {
+                                        record_proxy(&ctx, proxy, ProxyOutcome::Ok, &mut meta);
+                                        let (text, encoding, charset_errors) =
+                                            decode_body(&headers, &body, ctx.fallback_encoding);
+                                        meta.charset = Some(encoding.name().to_string());
+                                        meta.charset_errors = charset_errors;
+                                        let result = moved_parse_pool
+                                            .run(move || moved_parser(&text))
+                                            .await
+                                            .map_err(ScrapeErr::from)
+                                            .and_then(|parsed| parsed.map_err(ScrapeErr::from));
+                                        let validators = result.as_ref().ok().and_then(|payload| {
+                                            Validators::from_headers(&headers, payload.clone())
+                                        });
+                                        return Scraped {
+                                            result,
+                                            meta,
+                                            validators,
+                                        };
+                                    }
+                                    StatusClass::Permanent => {
+                                        record_proxy(
+                                            &ctx,
+                                            proxy,
+                                            ProxyOutcome::PermanentStatus,
+                                            &mut meta,
+                                        );
+                                        return Scraped {
+                                            result: Err(ScrapeErr::HttpStatus {
+                                                status,
+                                                attempts: attempt,
+                                            }),
+                                            meta,
+                                            validators: None,
+                                        };
+                                    }
+                                    StatusClass::Retryable => (
+                                        parse_retry_after(&headers, SystemTime::now()),
+                                        ScrapeErr::HttpStatus {
+                                            status,
+                                            attempts: attempt,
+                                        },
+                                        ProxyOutcome::RetryableStatus,
+                                    ),
+                                }
+                            }
+                            Err(e) => {
+                                tracing::error!("{e}");
+                                (None, ScrapeErr::OverRetry, ProxyOutcome::TransportError)
+                            }
+                        }
                     }
                     Err(e) => {
                         tracing::error!("{e}");
-                        tempt += 1;
+                        (None, ScrapeErr::OverRetry, ProxyOutcome::TransportError)
                     }
-                }
+                };
+                record_proxy(&ctx, proxy, outcome, &mut meta);
+
+                let Some(delay) = moved_retry.next_delay(attempt, retry_after) else {
+                    return Scraped {
+                        result: Err(last_err),
+                        meta,
+                        validators: None,
+                    };
+                };
+                tracing::warn!(
+                    attempt,
+                    delay_ms = delay.as_millis() as u64,
+                    "retrying: {}",
+                    last_err.code()
+                );
+                tokio::time::sleep(delay).await;
             }
-            Err(ScrapeErr::OverRetry)
         })
     })
 }
@@ -0,0 +1,133 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use tokio::{
+    sync::Semaphore,
+    task::{JoinError, JoinSet},
+};
+use tokio_util::sync::CancellationToken;
+
+// cpu bound parsing runs here instead of on the tokio workers,
+// so network concurrency (SEMAPHORE_SIZE) and parse concurrency can be tuned independently
+pub struct ParsePool {
+    semaphore: Arc<Semaphore>,
+    concurrency: usize,
+
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicU64,
+    wait_micros: AtomicU64,
+}
+
+#[derive(Debug)]
+pub struct ParsePoolMetrics {
+    pub concurrency: usize,
+    pub queued: usize,
+    pub running: usize,
+    pub completed: u64,
+    pub avg_wait: Duration,
+}
+
+// decrements the counter even when the waiting future is dropped
+struct CountGuard<'a>(&'a AtomicUsize);
+
+impl<'a> CountGuard<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        CountGuard(counter)
+    }
+}
+
+impl Drop for CountGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ParsePool {
+    pub fn new(concurrency: usize) -> Self {
+        ParsePool {
+            semaphore: Arc::new(Semaphore::new(concurrency)),
+            concurrency,
+            queued: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            wait_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub async fn run<F, T>(&self, f: F) -> Result<T, JoinError>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let started = Instant::now();
+        let permit = {
+            let _queued = CountGuard::new(&self.queued);
+            self.semaphore.clone().acquire_owned().await.unwrap()
+        };
+        self.wait_micros
+            .fetch_add(started.elapsed().as_micros() as u64, Ordering::Relaxed);
+
+        let _running = CountGuard::new(&self.running);
+        // keep the caller's span, so parser logs (cpp included) carry the path
+        let span = tracing::Span::current();
+        let result = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let _entered = span.enter();
+            f()
+        })
+        .await;
+        self.completed.fetch_add(1, Ordering::Relaxed);
+
+        result
+    }
+
+    pub fn metrics(&self) -> ParsePoolMetrics {
+        let completed = self.completed.load(Ordering::Relaxed);
+        let wait_micros = self.wait_micros.load(Ordering::Relaxed);
+
+        ParsePoolMetrics {
+            concurrency: self.concurrency,
+            queued: self.queued.load(Ordering::Relaxed),
+            running: self.running.load(Ordering::Relaxed),
+            completed,
+            avg_wait: Duration::from_micros(wait_micros.checked_div(completed).unwrap_or(0)),
+        }
+    }
+}
+
+pub fn invoke_parse_pool_reporter(
+    set: &mut JoinSet<()>,
+    token: CancellationToken,
+    pool: Arc<ParsePool>,
+    interval: Duration,
+) {
+    set.spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let m = pool.metrics();
+                    tracing::info!(
+                        target: "metrics",
+                        concurrency = m.concurrency,
+                        queued = m.queued,
+                        running = m.running,
+                        completed = m.completed,
+                        avg_wait_ms = m.avg_wait.as_millis() as u64,
+                        "parse pool"
+                    );
+                },
+                _ = token.cancelled() => {
+                    break;
+                }
+            }
+        }
+    });
+}
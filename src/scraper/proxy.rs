@@ -0,0 +1,175 @@
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::scraper::{HttpClientConfig, HttpClientErr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProxySelection {
+    RoundRobin,
+    // fewest failures so far, round robin among equals
+    LeastFailures,
+}
+
+impl std::str::FromStr for ProxySelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round_robin" => Ok(ProxySelection::RoundRobin),
+            "least_failures" => Ok(ProxySelection::LeastFailures),
+            _ => Err(format!("unknown proxy selection: {s}")),
+        }
+    }
+}
+
+struct ProxyHealth {
+    url: String,
+    // url without credentials, for logs and response metadata
+    label: String,
+    consecutive_failures: AtomicU32,
+    failures: AtomicU64,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+// egress proxies of the process, health is shared by every path,
+// each path builds its own client per proxy (see HttpClientConfig::build_with_proxy)
+pub struct ProxyPool {
+    proxies: Vec<ProxyHealth>,
+    selection: ProxySelection,
+    next: AtomicUsize,
+    // consecutive failures (transport errors, 429 / 5xx) before a proxy is ejected
+    max_failures: u32,
+    ejection: Duration,
+}
+
+fn redact(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) => {
+            let _ = parsed.set_username("");
+            let _ = parsed.set_password(None);
+            parsed.to_string()
+        }
+        Err(_) => "<invalid proxy url>".to_string(),
+    }
+}
+
+impl ProxyPool {
+    // None for an empty list, i.e. direct
+    pub fn new(
+        urls: Vec<String>,
+        selection: ProxySelection,
+        max_failures: u32,
+        ejection: Duration,
+    ) -> Option<Self> {
+        if urls.is_empty() {
+            return None;
+        }
+
+        Some(ProxyPool {
+            proxies: urls
+                .into_iter()
+                .map(|url| ProxyHealth {
+                    label: redact(&url),
+                    url,
+                    consecutive_failures: AtomicU32::new(0),
+                    failures: AtomicU64::new(0),
+                    ejected_until: Mutex::new(None),
+                })
+                .collect(),
+            selection,
+            next: AtomicUsize::new(0),
+            max_failures: max_failures.max(1),
+            ejection,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.proxies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty()
+    }
+
+    pub fn url(&self, idx: usize) -> &str {
+        &self.proxies[idx].url
+    }
+
+    pub fn label(&self, idx: usize) -> &str {
+        &self.proxies[idx].label
+    }
+
+    pub fn is_available(&self, idx: usize) -> bool {
+        self.ejected_until(idx)
+            .is_none_or(|until| until <= Instant::now())
+    }
+
+    fn ejected_until(&self, idx: usize) -> Option<Instant> {
+        *self.proxies[idx].ejected_until.lock().unwrap()
+    }
+
+    pub fn pick(&self) -> usize {
+        let len = self.proxies.len();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % len;
+        let mut available = (0..len)
+            .map(|offset| (start + offset) % len)
+            .filter(|idx| self.is_available(*idx));
+
+        let picked = match self.selection {
+            ProxySelection::RoundRobin => available.next(),
+            // min_by_key keeps the first of equals, i.e. the round robin order
+            ProxySelection::LeastFailures => {
+                available.min_by_key(|idx| self.proxies[*idx].failures.load(Ordering::Relaxed))
+            }
+        };
+
+        // all ejected, the one back first rather than stalling the path
+        picked.unwrap_or_else(|| {
+            (0..len)
+                .min_by_key(|idx| self.ejected_until(*idx))
+                .unwrap_or(0)
+        })
+    }
+
+    pub fn report(&self, idx: usize, ok: bool) {
+        let proxy = &self.proxies[idx];
+        if ok {
+            proxy.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        proxy.failures.fetch_add(1, Ordering::Relaxed);
+        let consecutive = proxy.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if consecutive >= self.max_failures {
+            proxy.consecutive_failures.store(0, Ordering::Relaxed);
+            *proxy.ejected_until.lock().unwrap() = Some(Instant::now() + self.ejection);
+            tracing::warn!(
+                proxy = %proxy.label,
+                failures = consecutive,
+                ejection_secs = self.ejection.as_secs(),
+                "proxy ejected"
+            );
+        }
+    }
+}
+
+// the clients of one path, one per proxy in the pool order
+pub struct ProxyClients {
+    pub pool: Arc<ProxyPool>,
+    pub clients: Vec<reqwest::Client>,
+}
+
+impl ProxyClients {
+    pub fn build(pool: Arc<ProxyPool>, config: &HttpClientConfig) -> Result<Self, HttpClientErr> {
+        let clients = (0..pool.len())
+            .map(|idx| config.build_with_proxy(Some(pool.url(idx))))
+            .collect::<Result<_, _>>()?;
+
+        Ok(ProxyClients { pool, clients })
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use bb8::Pool;
+use bb8_redis::RedisConnectionManager;
+
+use crate::redis_lib::reserve_rate_limit;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// token bucket per target host, one for the whole process so every path
+// hitting the same site shares its budget (SEMAPHORE_SIZE only caps concurrency)
+pub struct HostRateLimiter {
+    // 0 disables the limiter
+    rps: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    // from robots.txt, one request per delay with no burst
+    crawl_delays: Mutex<HashMap<String, Duration>>,
+}
+
+impl HostRateLimiter {
+    pub fn new(rps: f64, burst: u32) -> Self {
+        HostRateLimiter {
+            rps,
+            burst: burst.max(1) as f64,
+            buckets: Mutex::new(HashMap::new()),
+            crawl_delays: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_crawl_delay(&self, host: &str, delay: Duration) {
+        self.crawl_delays
+            .lock()
+            .unwrap()
+            .insert(host.to_string(), delay);
+    }
+
+    // takes a token right away, the returned wait is how long until it is actually ours,
+    // so concurrent callers queue up instead of waking together
+    pub fn reserve(&self, host: &str) -> Duration {
+        let crawl_delay = self.crawl_delays.lock().unwrap().get(host).copied();
+        let (rps, burst) = match crawl_delay {
+            // unless the configured rate is lower still
+            Some(delay) if !delay.is_zero() => {
+                let delay_rps = 1.0 / delay.as_secs_f64();
+                let rps = if self.rps > 0.0 {
+                    self.rps.min(delay_rps)
+                } else {
+                    delay_rps
+                };
+                (rps, 1.0)
+            }
+            _ => (self.rps, self.burst),
+        };
+        if rps <= 0.0 {
+            return Duration::ZERO;
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(host.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let refill = now.duration_since(bucket.updated).as_secs_f64() * rps;
+        bucket.tokens = (bucket.tokens + refill).min(burst) - 1.0;
+        bucket.updated = now;
+
+        if bucket.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-bucket.tokens / rps)
+        }
+    }
+
+    pub async fn acquire(&self, host: &str) {
+        let wait = self.reserve(host);
+        if !wait.is_zero() {
+            tracing::debug!(host, wait_ms = wait.as_millis() as u64, "rate limited");
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+// one budget per host over every replica, see redis_lib::reserve_rate_limit
+pub struct ClusterRateLimiter {
+    pool: Arc<Pool<RedisConnectionManager>>,
+    key_prefix: String,
+    interval: Duration,
+    burst: u32,
+    // redis gets this long before the fallback is used for the request
+    redis_timeout: Duration,
+    // conservative, i.e. the cluster budget split by the replica count
+    fallback: HostRateLimiter,
+    degraded: AtomicBool,
+    crawl_delays: Mutex<HashMap<String, Duration>>,
+}
+
+impl ClusterRateLimiter {
+    pub fn new(
+        pool: Arc<Pool<RedisConnectionManager>>,
+        key_prefix: String,
+        rps: f64,
+        burst: u32,
+        redis_timeout: Duration,
+        fallback: HostRateLimiter,
+    ) -> Self {
+        ClusterRateLimiter {
+            pool,
+            key_prefix,
+            interval: Duration::from_secs_f64(1.0 / rps),
+            burst,
+            redis_timeout,
+            fallback,
+            degraded: AtomicBool::new(false),
+            crawl_delays: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_crawl_delay(&self, host: &str, delay: Duration) {
+        self.crawl_delays
+            .lock()
+            .unwrap()
+            .insert(host.to_string(), delay);
+        self.fallback.set_crawl_delay(host, delay);
+    }
+
+    async fn reserve(&self, host: &str) -> Option<Duration> {
+        let key = format!("{}:{host}", self.key_prefix);
+        let (interval, burst) = match self.crawl_delays.lock().unwrap().get(host) {
+            Some(delay) => (self.interval.max(*delay), 1),
+            None => (self.interval, self.burst),
+        };
+        let pool = self.pool.clone();
+        // spawned, the redis future is not Sync as the scraper future has to be
+        // a timed out reservation still lands, which only makes the budget stricter
+        let reserving = tokio::spawn(async move {
+            let mut conn = pool.get().await.ok()?;
+            reserve_rate_limit(&key, interval, burst, &mut conn)
+                .await
+                .inspect_err(|e| tracing::debug!("{e}"))
+                .ok()
+        });
+
+        tokio::time::timeout(self.redis_timeout, reserving)
+            .await
+            .ok()?
+            .ok()
+            .flatten()
+    }
+
+    pub async fn acquire(&self, host: &str) {
+        match self.reserve(host).await {
+            Some(wait) => {
+                if self.degraded.swap(false, Ordering::Relaxed) {
+                    tracing::warn!("cluster rate limit is back on redis");
+                }
+                if !wait.is_zero() {
+                    tracing::debug!(host, wait_ms = wait.as_millis() as u64, "rate limited");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+            None => {
+                if !self.degraded.swap(true, Ordering::Relaxed) {
+                    tracing::error!(
+                        "redis unreachable, cluster rate limit falls back to the local one"
+                    );
+                }
+                self.fallback.acquire(host).await;
+            }
+        }
+    }
+}
+
+pub enum RateLimiter {
+    Local(HostRateLimiter),
+    Cluster(ClusterRateLimiter),
+}
+
+impl RateLimiter {
+    pub async fn acquire(&self, host: &str) {
+        match self {
+            RateLimiter::Local(limiter) => limiter.acquire(host).await,
+            RateLimiter::Cluster(limiter) => limiter.acquire(host).await,
+        }
+    }
+
+    // Crawl-delay of robots.txt, see RobotsPolicy
+    pub fn set_crawl_delay(&self, host: &str, delay: Duration) {
+        match self {
+            RateLimiter::Local(limiter) => limiter.set_crawl_delay(host, delay),
+            RateLimiter::Cluster(limiter) => limiter.set_crawl_delay(host, delay),
+        }
+    }
+}
+
+// requests without a host (a broken url) share one bucket and fail on send anyway
+pub fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .unwrap_or_default()
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, SystemTime};
+
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StatusClass {
+    Success,
+    // 429, 5xx
+    Retryable,
+    // 404, 410 and every other status, parsing an error page is pointless
+    Permanent,
+}
+
+pub fn classify(status: StatusCode) -> StatusClass {
+    if status.is_success() {
+        StatusClass::Success
+    } else if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        StatusClass::Retryable
+    } else {
+        StatusClass::Permanent
+    }
+}
+
+// seconds or an http date, a date in the past means right away
+pub fn parse_retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+
+    Some(at.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    // in total, the first request included (NET_REQUEST_RETRY)
+    pub max_attempts: i32,
+    pub init_backoff: Duration,
+    pub max_backoff: Duration,
+    // each backoff is drawn from [1 - jitter, 1 + jitter] times itself
+    pub jitter: f64,
+    // a longer Retry-After gives up instead of holding the semaphore permit
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            init_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.5,
+            max_retry_after: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // after the `attempt`th failure, 1 based
+    pub fn backoff(&self, attempt: i32) -> Duration {
+        let exp = attempt.saturating_sub(1).clamp(0, 31) as u32;
+        let base = self
+            .init_backoff
+            .saturating_mul(2u32.saturating_pow(exp))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        let factor = if jitter > 0.0 {
+            rand::random_range(1.0 - jitter..=1.0 + jitter)
+        } else {
+            1.0
+        };
+
+        base.mul_f64(factor).min(self.max_backoff)
+    }
+
+    // wait before the next attempt, None gives up
+    pub fn next_delay(&self, attempt: i32, retry_after: Option<Duration>) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        match retry_after {
+            Some(wait) if wait > self.max_retry_after => None,
+            Some(wait) => Some(wait),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use std::{sync::Arc, time::Duration};
+
+use bb8::PooledConnection;
+use bb8_redis::RedisConnectionManager;
+use regex::Regex;
+
+use crate::{
+    redis_lib::{RedisLibErr, cache_robots_txt, get_robots_txt},
+    scraper::{RateLimiter, StatusClass, classify, host_of},
+};
+
+// RFC 9309, larger files are cut here
+const MAX_ROBOTS_TXT: usize = 500 * 1024;
+
+struct Rule {
+    allow: bool,
+    // pattern length, the longest matching rule wins
+    len: usize,
+    pattern: Regex,
+}
+
+// the groups of one robots.txt that apply to our user agent, merged
+pub struct RobotsRules {
+    rules: Vec<Rule>,
+    pub crawl_delay: Option<Duration>,
+}
+
+#[derive(Default)]
+struct Group {
+    agents: Vec<String>,
+    // (allow, pattern)
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<f64>,
+}
+
+// "*" is any sequence, a trailing "$" anchors the end
+fn compile_pattern(pattern: &str) -> Option<Regex> {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let body = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+
+    Regex::new(&format!("^{body}{}", if anchored { "$" } else { "" })).ok()
+}
+
+impl RobotsRules {
+    // groups naming our product token win over "*" ones, as in RFC 9309
+    pub fn parse(body: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_ascii_lowercase();
+
+        let mut groups: Vec<Group> = vec![];
+        let mut in_agents = false;
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim());
+
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push(Group::default());
+                        in_agents = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                "allow" | "disallow" | "crawl-delay" => {
+                    in_agents = false;
+                    let Some(group) = groups.last_mut() else {
+                        continue;
+                    };
+                    match key.as_str() {
+                        "crawl-delay" => group.crawl_delay = value.parse::<f64>().ok(),
+                        // an empty disallow allows everything, i.e. no rule
+                        _ if value.is_empty() => {}
+                        _ => group.rules.push((key == "allow", value.to_string())),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let named: Vec<_> = groups
+            .iter()
+            .filter(|group| group.agents.contains(&user_agent))
+            .collect();
+        let applied = if named.is_empty() {
+            groups
+                .iter()
+                .filter(|group| group.agents.iter().any(|agent| agent == "*"))
+                .collect()
+        } else {
+            named
+        };
+
+        let mut rules = vec![];
+        let mut crawl_delay = None;
+        for group in applied {
+            for (allow, pattern) in &group.rules {
+                if let Some(compiled) = compile_pattern(pattern) {
+                    rules.push(Rule {
+                        allow: *allow,
+                        len: pattern.len(),
+                        pattern: compiled,
+                    });
+                }
+            }
+            crawl_delay = crawl_delay.or(group.crawl_delay.filter(|delay| *delay >= 0.0));
+        }
+
+        RobotsRules {
+            rules,
+            crawl_delay: crawl_delay.map(Duration::from_secs_f64),
+        }
+    }
+
+    // path with the query, e.g. "/search?q=a"
+    pub fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.pattern.is_match(path))
+            // allow wins a tie
+            .max_by_key(|rule| (rule.len, rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum RobotsErr {
+    #[error("{0}")]
+    RedisLib(#[from] RedisLibErr),
+}
+
+// opt-in, checked in the prior stage so disallowed urls are never fetched
+pub struct RobotsPolicy {
+    pub client: reqwest::Client,
+    // product token matched against User-agent lines
+    pub user_agent: String,
+    pub key_prefix: String,
+    // seconds
+    pub cache_ttl: usize,
+    // Crawl-delay lowers the rate of the host
+    pub rate_limiter: Arc<RateLimiter>,
+}
+
+impl RobotsPolicy {
+    // a missing robots.txt (4xx) allows everything and is cached as such,
+    // a failed fetch (5xx, network) allows this url and is retried on the next one
+    async fn fetch(&self, robots_url: &str) -> Option<String> {
+        self.rate_limiter.acquire(&host_of(robots_url)).await;
+        let response = match self.client.get(robots_url).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::warn!(robots_url, "{e}");
+                return None;
+            }
+        };
+
+        match classify(response.status()) {
+            StatusClass::Success => match response.text().await {
+                Ok(mut body) => {
+                    if body.len() > MAX_ROBOTS_TXT {
+                        let mut end = MAX_ROBOTS_TXT;
+                        while !body.is_char_boundary(end) {
+                            end -= 1;
+                        }
+                        body.truncate(end);
+                    }
+                    Some(body)
+                }
+                Err(e) => {
+                    tracing::warn!(robots_url, "{e}");
+                    None
+                }
+            },
+            StatusClass::Retryable => {
+                tracing::warn!(robots_url, status = %response.status(), "robots.txt unavailable");
+                None
+            }
+            StatusClass::Permanent => Some(String::new()),
+        }
+    }
+
+    pub async fn is_allowed(
+        &self,
+        url: &str,
+        conn: &mut PooledConnection<'_, RedisConnectionManager>,
+    ) -> Result<bool, RobotsErr> {
+        // a broken url fails on fetch with its own error
+        let Ok(parsed) = reqwest::Url::parse(url) else {
+            return Ok(true);
+        };
+        let Ok(robots_url) = parsed.join("/robots.txt") else {
+            return Ok(true);
+        };
+
+        let key = format!(
+            "{}:{}",
+            self.key_prefix,
+            robots_url.origin().ascii_serialization()
+        );
+        let body = match get_robots_txt(&key, conn).await? {
+            Some(body) => body,
+            None => match self.fetch(robots_url.as_str()).await {
+                Some(body) => {
+                    cache_robots_txt(&key, &body, self.cache_ttl, conn).await?;
+                    body
+                }
+                None => return Ok(true),
+            },
+        };
+
+        let rules = RobotsRules::parse(&body, &self.user_agent);
+        if let Some(delay) = rules.crawl_delay {
+            self.rate_limiter.set_crawl_delay(&host_of(url), delay);
+        }
+        let path = match parsed.query() {
+            Some(query) => format!("{}?{query}", parsed.path()),
+            None => parsed.path().to_string(),
+        };
+
+        Ok(rules.is_allowed(&path))
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use crate::redis_communication::RedisResponse;
+
+// a markup change on the site rarely fails the parsers, fields just come back empty,
+// so the last `window` scrapes of a path are kept to watch empty and error rates
+#[derive(Clone, Debug)]
+pub struct DriftConfig {
+    pub window: usize,
+    // rates are not judged until the window holds this many scrapes
+    pub min_samples: usize,
+    // over successful parses
+    pub empty_rate_threshold: f64,
+    // over all scrapes, per error code
+    pub error_rate_threshold: f64,
+    // top level fields of an object payload, e.g. cv, genre, time_table of meta
+    // the payload itself (an empty detail / tag array) is always watched as "payload"
+    pub fields: Vec<String>,
+}
+
+impl DriftConfig {
+    // an empty window judges nothing, a min_samples past the window is never reached
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window == 0 {
+            return Err("DRIFT_WINDOW must be positive".to_string());
+        }
+        if self.min_samples > self.window {
+            return Err(format!(
+                "DRIFT_MIN_SAMPLES ({}) must not exceed DRIFT_WINDOW ({})",
+                self.min_samples, self.window
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DriftSignal {
+    EmptyField(String),
+    ErrorCode(String),
+}
+
+impl std::fmt::Display for DriftSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DriftSignal::EmptyField(field) => write!(f, "empty:{field}"),
+            DriftSignal::ErrorCode(code) => write!(f, "error:{code}"),
+        }
+    }
+}
+
+// a signal crossing its threshold, either way
+#[derive(Debug, PartialEq)]
+pub struct DriftChange {
+    pub signal: DriftSignal,
+    pub rate: f64,
+    pub drifting: bool,
+}
+
+enum Outcome {
+    // empty flags, "payload" first and then config.fields
+    Parsed(Vec<bool>),
+    Failed(String),
+}
+
+pub struct DriftTracker {
+    path: String,
+    config: DriftConfig,
+    window: VecDeque<Outcome>,
+
+    parsed: usize,
+    empty_counts: Vec<usize>,
+    error_counts: BTreeMap<String, usize>,
+    drifting: BTreeSet<DriftSignal>,
+}
+
+fn is_empty(value: Option<&serde_json::Value>) -> bool {
+    match value {
+        None | Some(serde_json::Value::Null) => true,
+        Some(serde_json::Value::String(s)) => s.is_empty(),
+        Some(serde_json::Value::Array(a)) => a.is_empty(),
+        Some(serde_json::Value::Object(o)) => o.is_empty(),
+        Some(_) => false,
+    }
+}
+
+impl DriftTracker {
+    pub fn new(path: String, config: DriftConfig) -> Self {
+        DriftTracker {
+            path,
+            empty_counts: vec![0; config.fields.len() + 1],
+            window: VecDeque::with_capacity(config.window),
+            config,
+            parsed: 0,
+            error_counts: BTreeMap::new(),
+            drifting: BTreeSet::new(),
+        }
+    }
+
+    fn outcome_of(&self, response: &RedisResponse) -> Outcome {
+        if let Some(code) = &response.error_code {
+            return Outcome::Failed(code.clone());
+        }
+
+        let payload: serde_json::Value = response
+            .payload
+            .as_deref()
+            .and_then(|payload| serde_json::from_str(payload).ok())
+            .unwrap_or_default();
+        let mut empty = vec![is_empty(Some(&payload))];
+        empty.extend(
+            self.config
+                .fields
+                .iter()
+                .map(|field| is_empty(payload.get(field))),
+        );
+        Outcome::Parsed(empty)
+    }
+
+    fn count(&mut self, outcome: &Outcome, add: bool) {
+        match outcome {
+            Outcome::Parsed(empty) => {
+                self.parsed = if add {
+                    self.parsed + 1
+                } else {
+                    self.parsed - 1
+                };
+                for (count, _) in self.empty_counts.iter_mut().zip(empty).filter(|(_, e)| **e) {
+                    *count = if add { *count + 1 } else { *count - 1 };
+                }
+            }
+            Outcome::Failed(code) => {
+                let count = self.error_counts.entry(code.clone()).or_default();
+                *count = if add { *count + 1 } else { *count - 1 };
+                if *count == 0 {
+                    self.error_counts.remove(code);
+                }
+            }
+        }
+    }
+
+    fn rates(&self) -> Vec<(DriftSignal, f64, f64)> {
+        let mut rates = vec![];
+        if self.parsed > 0 {
+            let names =
+                std::iter::once("payload").chain(self.config.fields.iter().map(String::as_str));
+            for (name, count) in names.zip(&self.empty_counts) {
+                rates.push((
+                    DriftSignal::EmptyField(name.to_string()),
+                    *count as f64 / self.parsed as f64,
+                    self.config.empty_rate_threshold,
+                ));
+            }
+        }
+
+        // codes that just left the window still have to clear
+        let codes: BTreeSet<&String> = self
+            .error_counts
+            .keys()
+            .chain(self.drifting.iter().filter_map(|signal| match signal {
+                DriftSignal::ErrorCode(code) => Some(code),
+                DriftSignal::EmptyField(_) => None,
+            }))
+            .collect();
+        for code in codes {
+            let count = self.error_counts.get(code).copied().unwrap_or(0);
+            rates.push((
+                DriftSignal::ErrorCode(code.clone()),
+                count as f64 / self.window.len() as f64,
+                self.config.error_rate_threshold,
+            ));
+        }
+
+        rates
+    }
+
+    // records one scrape, returns the signals that crossed their threshold with it
+    pub fn observe(&mut self, response: &RedisResponse) -> Vec<DriftChange> {
+        let outcome = self.outcome_of(response);
+        self.count(&outcome, true);
+        self.window.push_back(outcome);
+        if self.window.len() > self.config.window
+            && let Some(oldest) = self.window.pop_front()
+        {
+            self.count(&oldest, false);
+        }
+
+        if self.window.len() < self.config.min_samples {
+            return vec![];
+        }
+
+        let mut changes = vec![];
+        for (signal, rate, threshold) in self.rates() {
+            let drifting = rate > threshold;
+            if drifting == self.drifting.contains(&signal) {
+                continue;
+            }
+
+            if drifting {
+                tracing::error!(
+                    path = %self.path,
+                    signal = %signal,
+                    rate,
+                    threshold,
+                    window = self.window.len(),
+                    "LAYOUT DRIFT: the site markup may have changed"
+                );
+                self.drifting.insert(signal.clone());
+            } else {
+                tracing::warn!(path = %self.path, signal = %signal, rate, "layout drift cleared");
+                self.drifting.remove(&signal);
+            }
+            tracing::info!(
+                target: "metrics",
+                path = %self.path,
+                signal = %signal,
+                rate,
+                drifting,
+                "layout drift"
+            );
+            changes.push(DriftChange {
+                signal,
+                rate,
+                drifting,
+            });
+        }
+
+        changes
+    }
+}
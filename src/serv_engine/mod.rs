@@ -1,7 +1,11 @@
+pub mod drift;
 mod post_process;
 mod prior_process;
+pub mod replay;
 mod scrape_process;
 
+pub use scrape_process::{Delivery, ScrapeResultItem};
+
 use std::{pin::Pin, sync::Arc};
 
 use bb8::Pool;
@@ -16,16 +20,20 @@ use crate::{
         ClientAcquireConfig, PoolAcquireConfig, RedisLibErr, ReqFetchContract, RequestFetcherErr,
         invoke_req_fetcher,
     },
-    scraper::ScrapeErr,
+    scraper::{
+        ArchiveSink, ConditionalGet, FetchContext, HttpClientConfig, HttpClientErr, ProxyClients,
+        ProxyPool, RobotsPolicy, ScrapeErr, Scraped, Validators,
+    },
     serv_engine::{
-        post_process::{PostProcessErr, invoke_post_process},
-        prior_process::{PriorProcessErr, invoke_prior_process},
-        scrape_process::{ScrapeProcessErr, invoke_scrape_process},
+        drift::DriftConfig,
+        post_process::{PostProcessContract, PostProcessErr, invoke_post_process},
+        prior_process::{PriorProcessContract, PriorProcessErr, invoke_prior_process},
+        scrape_process::{ScrapeProcessContract, ScrapeProcessErr, invoke_scrape_process},
     },
     thread_handler::ThreadHandler,
 };
 
-type Scraper<Output> = dyn Fn(reqwest::Client, String) -> Pin<Box<dyn Future<Output = Output> + Send + Sync + 'static>>
+type Scraper<Output> = dyn Fn(FetchContext, String) -> Pin<Box<dyn Future<Output = Output> + Send + Sync + 'static>>
     + Send
     + Sync
     + 'static;
@@ -52,14 +60,35 @@ pub enum EngineErr {
 
     #[error("{0}")]
     InvokePostProcess(#[from] PostProcessErr),
+
+    #[error("{0}")]
+    HttpClientErr(#[from] HttpClientErr),
 }
 
 pub struct ProcessReqContract {
+    // used as tracing context, e.g. "meta"
+    pub name: String,
     pub result_keyword: String,
     pub storage_time: usize,
-    pub scraper: Arc<Scraper<Result<String, ScrapeErr>>>,
+    pub scraper: Arc<Scraper<Scraped>>,
     pub inner_buf: usize,
     pub semaphore: Arc<Semaphore>,
+    // listing paths (detail): mark every entry of the payload with "is_new"
+    pub flag_new_entries: bool,
+    pub drift: DriftConfig,
+    pub http_client: HttpClientConfig,
+    // opt-in, shared by every path
+    pub robots: Option<Arc<RobotsPolicy>>,
+    // shared health, each path builds a client per proxy
+    pub proxy_pool: Option<Arc<ProxyPool>>,
+    // default of the path, a request may override it
+    pub sticky_proxy: bool,
+    // opt-in, If-None-Match / If-Modified-Since on recrawls
+    pub conditional: Option<Arc<ConditionalGet>>,
+    // opt-in, raw bodies of every fetch
+    pub archive: Option<Arc<ArchiveSink>>,
+    // for pages that declare no charset
+    pub fallback_encoding: &'static encoding_rs::Encoding,
 }
 
 pub async fn create_path<RR>(
@@ -78,6 +107,25 @@ pub async fn create_path<RR>(
 where
     RR: RedisRequest + serde::de::DeserializeOwned,
 {
+    // before anything is spawned, a bad header in the config fails the path here
+    let fetch = FetchContext {
+        client: process_request_contract.http_client.build()?,
+        proxies: match &process_request_contract.proxy_pool {
+            Some(proxy_pool) => Some(Arc::new(ProxyClients::build(
+                proxy_pool.clone(),
+                &process_request_contract.http_client,
+            )?)),
+            None => None,
+        },
+        sticky_proxy: process_request_contract.sticky_proxy,
+        validators: None,
+        path_name: process_request_contract.name.clone(),
+        request_id: String::new(),
+        index: 0,
+        archive: process_request_contract.archive.clone(),
+        fallback_encoding: process_request_contract.fallback_encoding,
+    };
+
     let mut set = JoinSet::new();
     let token = CancellationToken::new();
 
@@ -104,6 +152,10 @@ where
         pool_config.clone(),
         req_rx,
         tx_of_process_info,
+        PriorProcessContract {
+            robots: process_request_contract.robots.clone(),
+            conditional: process_request_contract.conditional.clone(),
+        },
     )
     .await?;
 
@@ -111,10 +163,14 @@ where
     invoke_scrape_process(
         &mut set,
         token.child_token(),
-        process_request_contract.semaphore.clone(),
         rx_of_process_info,
         tx_of_scrape_result,
-        process_request_contract.scraper.clone(),
+        ScrapeProcessContract {
+            path_name: process_request_contract.name.clone(),
+            semaphore: process_request_contract.semaphore.clone(),
+            fetch,
+            scraper: process_request_contract.scraper.clone(),
+        },
     )
     .await?;
 
@@ -125,8 +181,14 @@ where
         pool.clone(),
         pool_config.clone(),
         rx_of_scrape_result,
-        process_request_contract.result_keyword,
-        process_request_contract.storage_time,
+        PostProcessContract {
+            path_name: process_request_contract.name,
+            result_keyword: process_request_contract.result_keyword,
+            storage_time: process_request_contract.storage_time,
+            flag_new_entries: process_request_contract.flag_new_entries,
+            drift: process_request_contract.drift,
+            conditional: process_request_contract.conditional,
+        },
     )
     .await?;
 
@@ -140,6 +202,12 @@ pub struct ProcessItem {
 
     pub url: String,
     pub need_request: bool,
+    // not fetched, answered with robots_disallowed
+    pub robots_disallowed: bool,
+    // overrides the sticky_proxy of the path
+    pub sticky_proxy: Option<bool>,
+    // stored by the last fetch, None when conditional get is off
+    pub validators: Option<Validators>,
 }
 
 pub fn create_identifier(url: &str) -> String {
@@ -7,39 +7,105 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{
     redis_lib::{
-        AcquireConfigTrait, PoolAcquireConfig, RedisLibErr, push_result, update_job_status,
-        update_recently_got,
+        AcquireConfigTrait, PoolAcquireConfig, RedisLibErr, is_recentry_got, push_result,
+        update_job_status, update_recently_got,
+    },
+    scraper::{ConditionalErr, ConditionalGet},
+    serv_engine::{
+        create_identifier,
+        drift::{DriftConfig, DriftTracker},
+        scrape_process::{Delivery, ScrapeResultItem},
     },
-    serv_engine::{create_identifier, scrape_process::ScrapeResultItem},
 };
 
 #[derive(thiserror::Error, Debug)]
 pub enum PostProcessErr {
     #[error("{0}")]
     RedisLib(#[from] RedisLibErr),
+
+    #[error("{0}")]
+    SerdeJsonErr(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    Conditional(#[from] ConditionalErr),
 }
 
 type ProcessResult<T> = Result<T, PostProcessErr>;
 
+pub struct PostProcessContract {
+    pub path_name: String,
+    pub result_keyword: String,
+    pub storage_time: usize,
+    pub flag_new_entries: bool,
+    pub drift: DriftConfig,
+    pub conditional: Option<Arc<ConditionalGet>>,
+}
+
+// listing payload ([{ "url", ... }, ...]) ---> each entry gets "is_new",
+// i.e. whether its url is missing from the recently got store
+async fn flag_new_entries(
+    payload: &str,
+    conn: &mut PooledConnection<'_, RedisConnectionManager>,
+) -> ProcessResult<String> {
+    let mut entries: Vec<serde_json::Map<String, serde_json::Value>> =
+        serde_json::from_str(payload)?;
+    for entry in entries.iter_mut() {
+        let Some(url) = entry.get("url").and_then(|url| url.as_str()) else {
+            continue;
+        };
+        let is_new = !is_recentry_got(&create_identifier(url), conn).await?;
+        entry.insert("is_new".to_string(), is_new.into());
+    }
+
+    Ok(serde_json::to_string(&entries)?)
+}
+
 async fn post_process_inner(
     conn: &mut PooledConnection<'_, RedisConnectionManager>,
-    scraped_result: ScrapeResultItem,
-    storage_time: usize,
-    result_keyword: &str,
+    mut scraped_result: ScrapeResultItem,
+    contract: &PostProcessContract,
 ) -> ProcessResult<()> {
-    let url_op = scraped_result.status_update_url;
-    if let Some(url) = url_op {
-        push_result(
-            result_keyword,
-            &scraped_result.id,
-            &scraped_result.send_content,
-            conn,
-        )
-        .await?;
-        update_job_status(&scraped_result.job_id, &scraped_result.id, conn).await?;
-        let identifier = create_identifier(&url);
-        update_recently_got(&identifier, conn, storage_time).await?;
+    let identifier = match scraped_result.delivery() {
+        Delivery::Skip => return Ok(()),
+        Delivery::Report => None,
+        Delivery::Record => scraped_result
+            .status_update_url
+            .as_deref()
+            .map(create_identifier),
     };
+    if let Some(identifier) = &identifier {
+        // the parsed payload, before the per fetch "is_new" flags
+        if let (Some(conditional), Some(validators)) =
+            (&contract.conditional, &scraped_result.validators)
+        {
+            if scraped_result
+                .response
+                .meta
+                .as_ref()
+                .is_some_and(|meta| meta.not_modified)
+            {
+                conditional.refresh(identifier, validators, conn).await?;
+            } else {
+                conditional.store(identifier, validators, conn).await?;
+            }
+        }
+        if contract.flag_new_entries
+            && let Some(payload) = &scraped_result.response.payload
+        {
+            scraped_result.response.payload = Some(flag_new_entries(payload, conn).await?);
+        }
+    }
+    push_result(
+        &contract.result_keyword,
+        &scraped_result.id,
+        &serde_json::to_string(&scraped_result.response)?,
+        conn,
+    )
+    .await?;
+    update_job_status(&scraped_result.job_id, &scraped_result.id, conn).await?;
+    if let Some(identifier) = &identifier {
+        update_recently_got(identifier, conn, contract.storage_time).await?;
+    }
 
     Ok::<_, PostProcessErr>(())
 }
@@ -52,16 +118,20 @@ pub async fn invoke_post_process(
     pool_config: Arc<PoolAcquireConfig>,
     mut scraped_result_rx: Receiver<ScrapeResultItem>,
 
-    result_keyword: String,
-    storage_time: usize,
+    contract: PostProcessContract,
 ) -> ProcessResult<()> {
     set.spawn(async move {
         tokio::select! {
             _ = async move {
             } => {
                 let mut conn = pool_config.acquire_anyway(&pool).await;
+                let mut drift = DriftTracker::new(contract.path_name.clone(), contract.drift.clone());
                 while let Some(item) = scraped_result_rx.recv().await {
-                    if let Err (e)  = post_process_inner(&mut conn, item, storage_time, &result_keyword).await {
+                    // recently got and disallowed items were not scraped
+                    if item.delivery() == Delivery::Record {
+                        drift.observe(&item.response);
+                    }
+                    if let Err (e)  = post_process_inner(&mut conn, item, &contract).await {
                         tracing::error!("{e}");
                         conn = pool_config.acquire_anyway(&pool).await;
                     }
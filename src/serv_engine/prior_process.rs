@@ -11,6 +11,7 @@ use tokio_util::sync::CancellationToken;
 use crate::{
     redis_communication::RedisRequest,
     redis_lib::{AcquireConfigTrait, PoolAcquireConfig, RedisLibErr, is_recentry_got},
+    scraper::{ConditionalErr, ConditionalGet, RobotsErr, RobotsPolicy},
     serv_engine::{ProcessItem, create_identifier},
 };
 
@@ -21,12 +22,24 @@ pub enum PriorProcessErr {
 
     #[error("{0}")]
     RedisLibErr(#[from] RedisLibErr),
+
+    #[error("{0}")]
+    Robots(#[from] RobotsErr),
+
+    #[error("{0}")]
+    Conditional(#[from] ConditionalErr),
+}
+
+// opt-in checks, shared by every path
+pub struct PriorProcessContract {
+    pub robots: Option<Arc<RobotsPolicy>>,
+    pub conditional: Option<Arc<ConditionalGet>>,
 }
 
 type ProcessResult<T> = Result<T, PriorProcessErr>;
 
 async fn check_if_recently_got(
-    url: &String,
+    url: &str,
     conn: &mut PooledConnection<'_, RedisConnectionManager>,
 ) -> ProcessResult<bool> {
     let identifier = create_identifier(url);
@@ -36,11 +49,12 @@ async fn check_if_recently_got(
 async fn _prior_process_inner<RR>(
     received: String,
     conn: &mut PooledConnection<'_, RedisConnectionManager>,
+    contract: &PriorProcessContract,
 ) -> ProcessResult<ProcessItem>
 where
     RR: serde::de::DeserializeOwned + RedisRequest,
 {
-    let (url, id, job_id, idx, is_forced) = {
+    let (url, id, job_id, idx, is_forced, sticky_proxy) = {
         let redis_req: RR = serde_json::from_str(&received)?;
         Ok::<_, serde_json::Error>((
             redis_req.get_url(),
@@ -48,11 +62,29 @@ where
             redis_req.get_job_id(),
             redis_req.index(),
             redis_req.is_forced(),
+            redis_req.sticky_proxy(),
         ))
     }?;
 
+    let need_request = is_forced || !check_if_recently_got(&url, conn).await?;
+    // forced requests too, robots.txt is about the site, not the cache
+    let robots_disallowed = match &contract.robots {
+        Some(robots) if need_request => !robots.is_allowed(&url, conn).await?,
+        _ => false,
+    };
+    // a forced request wants a full fetch
+    let validators = match &contract.conditional {
+        Some(conditional) if need_request && !robots_disallowed && !is_forced => {
+            conditional.load(&create_identifier(&url), conn).await?
+        }
+        _ => None,
+    };
+
     Ok(ProcessItem {
-        need_request: is_forced || !check_if_recently_got(&url, conn).await?,
+        need_request: need_request && !robots_disallowed,
+        robots_disallowed,
+        sticky_proxy,
+        validators,
         id,
         job_id,
         url,
@@ -68,6 +100,8 @@ pub async fn invoke_prior_process<RR>(
 
     mut receiver_from_redis: Receiver<String>,
     tx_of_process_info: Sender<ProcessItem>,
+
+    contract: PriorProcessContract,
 ) -> ProcessResult<()>
 where
     RR: RedisRequest + serde::de::DeserializeOwned,
@@ -82,6 +116,7 @@ where
                     match _prior_process_inner::<RR>(
                         received,
                         &mut conn,
+                        &contract,
                     ).await {
                         Ok(item) => {
                             if let Err(e) = tx_of_process_info.send(item).await {
@@ -0,0 +1,283 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bb8::Pool;
+use bb8_redis::RedisConnectionManager;
+use tokio::{sync::mpsc::error::SendError, task::JoinSet};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    parser::ParserErr,
+    redis_communication::RedisResponse,
+    redis_lib::PoolAcquireConfig,
+    scraper::{
+        ArchiveEntry, ArchiveErr, ArchiveHeader, ArchiveRecord, ArchiveSink, ParsePool, ScrapeErr,
+        decode_body, header_map,
+    },
+    serv_engine::{
+        drift::DriftConfig,
+        post_process::{PostProcessContract, PostProcessErr, invoke_post_process},
+        scrape_process::ScrapeResultItem,
+    },
+};
+
+pub const REPLAY_ARG: &str = "replay";
+
+pub type ReplayParser = Arc<dyn Fn(&str) -> Result<String, ParserErr> + Send + Sync>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayErr {
+    #[error("{0}")]
+    Archive(#[from] ArchiveErr),
+
+    #[error("{0}")]
+    PostProcess(#[from] PostProcessErr),
+
+    #[error("{0}")]
+    SendErr(#[from] Box<SendError<ScrapeResultItem>>),
+}
+
+// "2025-01-31" or "2025-01-31T12:00:00", utc ---> unix millis
+pub fn parse_date(value: &str) -> Option<u64> {
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time.trim_end_matches('Z'))),
+        None => (value, None),
+    };
+    let mut ymd = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (ymd.next()??, ymd.next()??, ymd.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let secs_of_day = match time {
+        Some(time) => {
+            let mut hms = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+            let (hour, minute) = (hms.next()??, hms.next()??);
+            let second = hms.next().unwrap_or(Some(0))?;
+            hour * 3600 + minute * 60 + second
+        }
+        None => 0,
+    };
+
+    // days from civil, Howard Hinnant
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    u64::try_from((days * 86400 + secs_of_day) * 1000).ok()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayFilter {
+    // all paths when empty
+    pub paths: Vec<String>,
+    // unix millis, since inclusive, until exclusive
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    // request id, identifier or url, any of them
+    pub ids: Vec<String>,
+}
+
+impl ReplayFilter {
+    // before reading the record
+    pub fn matches_entry(&self, entry: &ArchiveEntry) -> bool {
+        (self.paths.is_empty() || self.paths.contains(&entry.path))
+            && self.since.is_none_or(|since| entry.fetched_at >= since)
+            && self.until.is_none_or(|until| entry.fetched_at < until)
+    }
+
+    pub fn matches(&self, header: &ArchiveHeader) -> bool {
+        self.ids.is_empty()
+            || self
+                .ids
+                .iter()
+                .any(|id| *id == header.id || *id == header.identifier || *id == header.url)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReplayArgs {
+    pub filter: ReplayFilter,
+    pub job_id: Option<String>,
+}
+
+impl ReplayArgs {
+    // --path meta --since 2025-01-01 --until 2025-02-01 --id RJ01234567 --job-id replay
+    // --path and --id may repeat
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut filter = ReplayFilter::default();
+        let mut job_id = None;
+
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {flag}"))?;
+            let date = || parse_date(value).ok_or_else(|| format!("bad date for {flag}: {value}"));
+            match flag.as_str() {
+                "--path" => filter.paths.push(value.clone()),
+                "--since" => filter.since = Some(date()?),
+                "--until" => filter.until = Some(date()?),
+                "--id" => filter.ids.push(value.clone()),
+                "--job-id" => job_id = Some(value.clone()),
+                _ => return Err(format!("unknown flag: {flag}")),
+            }
+        }
+
+        Ok(ReplayArgs { filter, job_id })
+    }
+}
+
+pub struct ReplayContract {
+    pub path_name: String,
+    pub parser: ReplayParser,
+    pub parse_pool: Arc<ParsePool>,
+    pub archive: Arc<ArchiveSink>,
+    pub filter: ReplayFilter,
+    // as the live path, for pages that declare no charset
+    pub fallback_encoding: &'static encoding_rs::Encoding,
+    // the results are reported to this job like any other
+    pub job_id: String,
+
+    // as the live path, see PostProcessContract
+    pub result_keyword: String,
+    pub storage_time: usize,
+    pub flag_new_entries: bool,
+    pub drift: DriftConfig,
+    pub inner_buf: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+    pub replayed: usize,
+    pub parse_failed: usize,
+    // expired, undecodable, error pages only or archived without a request id
+    pub skipped: usize,
+}
+
+// the newest 2xx record, error pages are archived too but never reached the parser
+async fn newest_success(
+    archive: &ArchiveSink,
+    newest_first: &[ArchiveEntry],
+) -> Option<ArchiveRecord> {
+    for entry in newest_first {
+        match archive.read(entry).await {
+            Ok(Some(record)) if (200..300).contains(&record.header.status) => return Some(record),
+            Ok(_) => {}
+            Err(e) => tracing::error!(identifier = %entry.identifier, "{e}"),
+        }
+    }
+
+    None
+}
+
+// newest archived body of each identifier ---> current parser ---> post process
+pub async fn replay_path(
+    pool: Arc<Pool<RedisConnectionManager>>,
+    pool_config: Arc<PoolAcquireConfig>,
+    contract: ReplayContract,
+) -> Result<ReplaySummary, ReplayErr> {
+    let entries = contract
+        .archive
+        .list(std::slice::from_ref(&contract.path_name))
+        .await?;
+    // older fetches would be overwritten by the newest anyway
+    let mut by_identifier: HashMap<String, Vec<ArchiveEntry>> = HashMap::new();
+    for entry in entries
+        .into_iter()
+        .filter(|entry| contract.filter.matches_entry(entry))
+    {
+        by_identifier
+            .entry(entry.identifier.clone())
+            .or_default()
+            .push(entry);
+    }
+
+    let mut set = JoinSet::new();
+    let (scraped_result_tx, scraped_result_rx) = tokio::sync::mpsc::channel(contract.inner_buf);
+    invoke_post_process(
+        &mut set,
+        CancellationToken::new(),
+        pool,
+        pool_config,
+        scraped_result_rx,
+        PostProcessContract {
+            path_name: contract.path_name.clone(),
+            result_keyword: contract.result_keyword.clone(),
+            storage_time: contract.storage_time,
+            flag_new_entries: contract.flag_new_entries,
+            drift: contract.drift.clone(),
+            conditional: None,
+        },
+    )
+    .await?;
+
+    let mut summary = ReplaySummary::default();
+    for mut newest_first in by_identifier.into_values() {
+        newest_first.sort_by_key(|entry| std::cmp::Reverse(entry.fetched_at));
+        let Some(record) = newest_success(&contract.archive, &newest_first).await else {
+            summary.skipped += 1;
+            continue;
+        };
+        if !contract.filter.matches(&record.header) {
+            continue;
+        }
+        if record.header.id.is_empty() {
+            tracing::warn!(url = %record.header.url, "archived without a request id");
+            summary.skipped += 1;
+            continue;
+        }
+
+        let parser = contract.parser.clone();
+        let (text, _, _) = decode_body(
+            &header_map(&record.header.headers),
+            &record.body,
+            contract.fallback_encoding,
+        );
+        let result = contract
+            .parse_pool
+            .run(move || parser(&text))
+            .await
+            .map_err(ScrapeErr::from)
+            .and_then(|parsed| parsed.map_err(ScrapeErr::from));
+        let response = match result {
+            Ok(payload) => RedisResponse {
+                error: None,
+                error_code: None,
+                payload: Some(payload),
+                index: record.header.index,
+                meta: None,
+            },
+            Err(e) => {
+                summary.parse_failed += 1;
+                RedisResponse {
+                    error: Some(format!("{e}")),
+                    error_code: Some(e.code().to_string()),
+                    payload: None,
+                    index: record.header.index,
+                    meta: None,
+                }
+            }
+        };
+        summary.replayed += 1;
+
+        scraped_result_tx
+            .send(ScrapeResultItem {
+                id: record.header.id,
+                job_id: contract.job_id.clone(),
+                status_update_url: Some(record.header.url),
+                response,
+                validators: None,
+                robots_disallowed: false,
+            })
+            .await
+            .map_err(Box::new)?;
+    }
+
+    // post process ends once the channel is drained
+    drop(scraped_result_tx);
+    while set.join_next().await.is_some() {}
+
+    Ok(summary)
+}
@@ -7,13 +7,16 @@ use tokio::{
     task::JoinSet,
 };
 use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
 use crate::{
-    redis_communication::RedisResponse, redis_lib::RedisLibErr, scraper::ScrapeErr,
+    redis_communication::RedisResponse,
+    redis_lib::RedisLibErr,
+    scraper::{FetchContext, Scraped, Validators},
     serv_engine::ProcessItem,
 };
 
-type Scraper<Output> = dyn Fn(reqwest::Client, String) -> Pin<Box<dyn Future<Output = Output> + Send + Sync + 'static>>
+type Scraper<Output> = dyn Fn(FetchContext, String) -> Pin<Box<dyn Future<Output = Output> + Send + Sync + 'static>>
     + Send
     + Sync
     + 'static;
@@ -25,35 +28,66 @@ pub enum ScrapeProcessErr {
     RedisLib(#[from] RedisLibErr),
 
     #[error("{0}")]
-    SendErr(#[from] SendError<ScrapeResultItem>),
+    // boxed, the item carries the whole response
+    SendErr(#[from] Box<SendError<ScrapeResultItem>>),
 
     #[error("{0}")]
     SerdeJsonErr(#[from] serde_json::Error),
 }
 
 async fn get_response(
-    scraper: &Arc<Scraper<Result<String, ScrapeErr>>>,
+    scraper: &Arc<Scraper<Scraped>>,
     item: &ProcessItem,
-    http_client: reqwest::Client,
-) -> ProcessResult<RedisResponse> {
-    Ok(match item.need_request {
-        true => match (scraper)(http_client, item.url.clone()).await {
-            Ok(payload) => RedisResponse {
-                error: None,
+    fetch: FetchContext,
+) -> ProcessResult<(RedisResponse, Option<Validators>)> {
+    if item.robots_disallowed {
+        return Ok((
+            RedisResponse {
+                error: Some("disallowed by robots.txt".to_string()),
+                error_code: Some("robots_disallowed".to_string()),
+                payload: None,
                 index: item.idx,
-                payload: Some(payload),
+                meta: None,
             },
-            Err(e) => RedisResponse {
-                error: Some(format!("{e}")),
+            None,
+        ));
+    }
+
+    Ok(match item.need_request {
+        true => {
+            let Scraped {
+                result,
+                meta,
+                validators,
+            } = (scraper)(fetch, item.url.clone()).await;
+            let response = match result {
+                Ok(payload) => RedisResponse {
+                    error: None,
+                    error_code: None,
+                    index: item.idx,
+                    payload: Some(payload),
+                    meta: Some(meta),
+                },
+                Err(e) => RedisResponse {
+                    error: Some(format!("{e}")),
+                    error_code: Some(e.code().to_string()),
+                    payload: None,
+                    index: item.idx,
+                    meta: Some(meta),
+                },
+            };
+            (response, validators)
+        }
+        false => (
+            RedisResponse {
+                error: Some("not forced and ".to_string()),
+                error_code: Some("recently_got".to_string()),
                 payload: None,
                 index: item.idx,
+                meta: None,
             },
-        },
-        false => RedisResponse {
-            error: Some("not forced and ".to_string()),
-            payload: None,
-            index: item.idx,
-        },
+            None,
+        ),
     })
 }
 
@@ -61,19 +95,52 @@ pub struct ScrapeResultItem {
     pub id: String,
     pub job_id: String,
     pub status_update_url: Option<String>,
-    pub send_content: String,
+    pub response: RedisResponse,
+    // fresh or revalidated, stored for the next fetch
+    pub validators: Option<Validators>,
+    // not fetched, but still reported to the job
+    pub robots_disallowed: bool,
+}
+
+// what post process does with a result
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    // recently got, nothing to report
+    Skip,
+    // pushed and counted for the job only
+    Report,
+    // also stored as recently got and observed for drift
+    Record,
+}
+
+impl ScrapeResultItem {
+    pub fn delivery(&self) -> Delivery {
+        if self.status_update_url.is_some() {
+            Delivery::Record
+        } else if self.robots_disallowed {
+            Delivery::Report
+        } else {
+            Delivery::Skip
+        }
+    }
 }
 
 // assumed to be used in JoinSet
 // in other way needs to have independent lifetime
 async fn scrape_process(
-    scraper: Arc<Scraper<Result<String, ScrapeErr>>>,
-    item: ProcessItem,
-    http_client: reqwest::Client,
+    scraper: Arc<Scraper<Scraped>>,
+    mut item: ProcessItem,
+    mut fetch: FetchContext,
     scraped_result_tx: Sender<ScrapeResultItem>,
     _guard: OwnedSemaphorePermit,
 ) -> ProcessResult<()> {
-    let resp = get_response(&scraper, &item, http_client).await?;
+    if let Some(sticky) = item.sticky_proxy {
+        fetch.sticky_proxy = sticky;
+    }
+    fetch.validators = item.validators.take();
+    fetch.request_id = item.id.clone();
+    fetch.index = item.idx;
+    let (resp, validators) = get_response(&scraper, &item, fetch).await?;
 
     scraped_result_tx
         .send(ScrapeResultItem {
@@ -84,26 +151,41 @@ async fn scrape_process(
             } else {
                 None
             },
-            send_content: serde_json::to_string(&resp)?,
+            response: resp,
+            validators,
+            robots_disallowed: item.robots_disallowed,
         })
-        .await?;
+        .await
+        .map_err(Box::new)?;
 
     Ok(())
 }
 
+pub struct ScrapeProcessContract {
+    pub path_name: String,
+    pub semaphore: Arc<Semaphore>,
+    // clients built once per path, see HttpClientConfig
+    pub fetch: FetchContext,
+    pub scraper: Arc<Scraper<Scraped>>,
+}
+
 pub async fn invoke_scrape_process(
     set: &mut JoinSet<()>,
     token: CancellationToken,
 
-    semaphore: Arc<Semaphore>,
     mut process_item_rx: Receiver<ProcessItem>,
     scraped_result_tx: Sender<ScrapeResultItem>,
 
-    scraper: Arc<Scraper<Result<String, ScrapeErr>>>,
+    contract: ScrapeProcessContract,
 ) -> ProcessResult<()> {
     set.spawn(async move {
+        let ScrapeProcessContract {
+            path_name,
+            semaphore,
+            fetch,
+            scraper,
+        } = contract;
         let mut inner_set = JoinSet::new();
-        let client = reqwest::Client::new();
 
         loop {
             tokio::select! {
@@ -111,20 +193,21 @@ pub async fn invoke_scrape_process(
                     if let Some(item) = item {
                         let guard = semaphore.clone().acquire_owned().await.unwrap();
                         let move_scraper = scraper.clone();
-                        let move_client = client.clone();
+                        let move_fetch = fetch.clone();
                         let moved_tx = scraped_result_tx.clone();
+                        let span = tracing::info_span!("scrape", path = %path_name, id = %item.id);
 
                         inner_set.spawn(async move {
                             if let Err(e) = scrape_process(
                                 move_scraper,
                                 item,
-                                move_client,
+                                move_fetch,
                                 moved_tx,
                                 guard
                             ).await {
                                 tracing::error!("{e}");
                             }
-                        });
+                        }.instrument(span));
                     }
                 },
 
@@ -0,0 +1,149 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use rust::scraper::{
+    ArchiveErr, ArchiveHeader, ArchiveRecord, ArchiveSink, ArchiveStore, FetchContext,
+    HostRateLimiter, ParsePool, RateLimiter, RetryPolicy, generate_scraper, unix_millis,
+};
+
+fn record(path: &str, fetched_at: u64) -> ArchiveRecord {
+    ArchiveRecord {
+        header: ArchiveHeader {
+            path: path.to_string(),
+            id: "RJ01234567".to_string(),
+            index: 3,
+            identifier: "abc".to_string(),
+            url: "https://example.com/work/=/product_id/RJ01234567.html".to_string(),
+            fetched_at,
+            status: 200,
+            headers: vec![(
+                "content-type".to_string(),
+                "text/html; charset=shift_jis".to_string(),
+            )],
+        },
+        // raw bytes, newlines and non utf-8 included
+        body: b"<html>\n<body>\x82\xa0</body>\n</html>".to_vec(),
+    }
+}
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("archive-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn record_survives_the_round_trip() {
+    let record = record("meta", 1_700_000_000_000);
+    let compressed = record.encode(3).unwrap();
+    assert_eq!(ArchiveRecord::decode(&compressed).unwrap(), record);
+}
+
+#[test]
+fn garbage_is_reported_not_panicked_on() {
+    assert!(ArchiveRecord::decode(b"not zstd").is_err());
+    let no_header = zstd::encode_all(&b"no newline"[..], 3).unwrap();
+    assert!(matches!(
+        ArchiveRecord::decode(&no_header),
+        Err(ArchiveErr::Malformed)
+    ));
+}
+
+#[tokio::test]
+async fn disk_archive_is_pruned_after_retention() {
+    let dir = temp_dir("prune");
+    let sink = ArchiveSink {
+        store: ArchiveStore::Disk { dir: dir.clone() },
+        retention: Duration::from_secs(60),
+        level: 3,
+    };
+    let now = unix_millis(SystemTime::now());
+    let fresh = record("meta", now);
+    let expired = record("detail", now - 120_000);
+    sink.write(&fresh).await.unwrap();
+    sink.write(&expired).await.unwrap();
+
+    let written = std::fs::read(dir.join("meta").join(format!("{}.zst", fresh.name()))).unwrap();
+    assert_eq!(ArchiveRecord::decode(&written).unwrap(), fresh);
+
+    assert_eq!(sink.prune(SystemTime::now()).await.unwrap(), 1);
+    assert!(
+        dir.join("meta")
+            .join(format!("{}.zst", fresh.name()))
+            .exists()
+    );
+    assert!(
+        !dir.join("detail")
+            .join(format!("{}.zst", expired.name()))
+            .exists()
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn error_pages_are_archived_with_their_status() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/work", listener.local_addr().unwrap());
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut request = vec![0; 4096];
+        let _ = stream.read(&mut request).await.unwrap();
+        let response = "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\n\
+            Content-Length: 22\r\n\r\n<html>not found</html>";
+        stream.write_all(response.as_bytes()).await.unwrap();
+    });
+
+    let dir = temp_dir("error-page");
+    let sink = Arc::new(ArchiveSink {
+        store: ArchiveStore::Disk { dir: dir.clone() },
+        retention: Duration::from_secs(60),
+        level: 3,
+    });
+    let scraper = generate_scraper(
+        |_: &str| Ok("{}".to_string()),
+        RetryPolicy::default(),
+        Arc::new(ParsePool::new(1)),
+        Arc::new(RateLimiter::Local(HostRateLimiter::new(100.0, 10))),
+    );
+    let ctx = FetchContext {
+        client: reqwest::Client::new(),
+        proxies: None,
+        sticky_proxy: false,
+        validators: None,
+        path_name: "meta".to_string(),
+        request_id: "RJ01234567".to_string(),
+        index: 0,
+        archive: Some(sink.clone()),
+        fallback_encoding: encoding_rs::UTF_8,
+    };
+
+    let scraped = scraper(ctx, url.clone()).await;
+    server.await.unwrap();
+    assert!(scraped.result.is_err());
+    assert_eq!(scraped.meta.status, Some(404));
+
+    // written in the background
+    let mut entries = vec![];
+    for _ in 0..50 {
+        entries = sink.list(&[]).await.unwrap();
+        if !entries.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(entries.len(), 1);
+    let record = sink.read(&entries[0]).await.unwrap().unwrap();
+    assert_eq!(record.header.status, 404);
+    assert_eq!(record.header.url, url);
+    assert_eq!(record.body, b"<html>not found</html>");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
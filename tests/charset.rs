@@ -0,0 +1,95 @@
+use encoding_rs::{EUC_JP, SHIFT_JIS, UTF_8, WINDOWS_1252};
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+
+use rust::scraper::{CharsetSource, decode_body, parse_encoding, sniff_charset};
+
+const TITLE: &str = "音声作品のタイトル";
+
+fn content_type(value: &'static str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(value));
+    headers
+}
+
+fn page(meta: &str) -> String {
+    format!("<html><head>{meta}<title>{TITLE}</title></head><body></body></html>")
+}
+
+#[test]
+fn meta_charset_decodes_shift_jis_without_header() {
+    let html = page(r#"<meta http-equiv="Content-Type" content="text/html; charset=Shift_JIS">"#);
+    let (body, _, _) = SHIFT_JIS.encode(&html);
+    let headers = content_type("text/html");
+
+    assert_eq!(
+        sniff_charset(&headers, &body, UTF_8),
+        (SHIFT_JIS, CharsetSource::Meta)
+    );
+    let (text, encoding, _) = decode_body(&headers, &body, UTF_8);
+    assert_eq!(encoding, SHIFT_JIS);
+    assert!(text.contains(TITLE));
+}
+
+#[test]
+fn header_wins_over_meta() {
+    let html = page(r#"<meta charset="shift_jis">"#);
+    let (body, _, _) = EUC_JP.encode(&html);
+    let headers = content_type("text/html; charset=\"EUC-JP\"");
+
+    let (text, encoding, _) = decode_body(&headers, &body, UTF_8);
+    assert_eq!(encoding, EUC_JP);
+    assert!(text.contains(TITLE));
+}
+
+#[test]
+fn bom_wins_over_everything() {
+    let mut body = b"\xEF\xBB\xBF".to_vec();
+    body.extend(page(r#"<meta charset="euc-jp">"#).as_bytes());
+    let headers = content_type("text/html; charset=shift_jis");
+
+    assert_eq!(
+        sniff_charset(&headers, &body, SHIFT_JIS),
+        (UTF_8, CharsetSource::Bom)
+    );
+    let (text, _, _) = decode_body(&headers, &body, SHIFT_JIS);
+    assert!(text.starts_with("<html>"));
+}
+
+#[test]
+fn fallback_applies_when_nothing_is_declared() {
+    let html = page("");
+    let (body, _, _) = SHIFT_JIS.encode(&html);
+    let (text, encoding, had_errors) = decode_body(&HeaderMap::new(), &body, SHIFT_JIS);
+    assert!(!had_errors);
+    assert_eq!(encoding, SHIFT_JIS);
+    assert!(text.contains(TITLE));
+}
+
+#[test]
+fn utf_16_in_meta_means_utf_8() {
+    let body = page(r#"<meta charset="utf-16">"#);
+    assert_eq!(
+        sniff_charset(&HeaderMap::new(), body.as_bytes(), WINDOWS_1252),
+        (UTF_8, CharsetSource::Meta)
+    );
+}
+
+#[test]
+fn malformed_bytes_are_replaced_and_flagged() {
+    let html = page("");
+    let (body, _, _) = SHIFT_JIS.encode(&html);
+    let headers = content_type("text/html; charset=utf-8");
+
+    let (text, encoding, had_errors) = decode_body(&headers, &body, UTF_8);
+    assert_eq!(encoding, UTF_8);
+    assert!(had_errors);
+    assert!(text.contains('\u{FFFD}'));
+    assert!(text.starts_with("<html><head><title>"));
+}
+
+#[test]
+fn labels_follow_the_encoding_standard() {
+    assert_eq!(parse_encoding("Windows-31J"), Some(SHIFT_JIS));
+    assert_eq!(parse_encoding(" euc-jp "), Some(EUC_JP));
+    assert_eq!(parse_encoding("klingon"), None);
+}
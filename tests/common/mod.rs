@@ -0,0 +1,22 @@
+use std::{fs, path::Path};
+
+// every tests/fixtures/<dir>/*.html, with its path and contents
+pub fn for_each_fixture(dir: &str, mut f: impl FnMut(&Path, &str)) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(dir);
+
+    let mut checked = 0;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "html") {
+            continue;
+        }
+
+        let html = fs::read_to_string(&path).unwrap();
+        f(&path, &html);
+        checked += 1;
+    }
+
+    assert!(checked > 0, "no fixture in {}", dir.display());
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use reqwest::header::{
+    ETAG, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use rust::scraper::{
+    FetchContext, HostRateLimiter, ParsePool, RateLimiter, RetryPolicy, Validators,
+    generate_scraper,
+};
+
+const LAST_MODIFIED_AT: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+
+#[test]
+fn validators_come_from_etag_and_last_modified() {
+    let mut headers = HeaderMap::new();
+    headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+    headers.insert(LAST_MODIFIED, HeaderValue::from_static(LAST_MODIFIED_AT));
+
+    let validators = Validators::from_headers(&headers, "{}".to_string()).unwrap();
+    assert_eq!(validators.etag.as_deref(), Some("\"abc\""));
+    assert_eq!(validators.last_modified.as_deref(), Some(LAST_MODIFIED_AT));
+    assert_eq!(validators.payload, "{}");
+}
+
+#[test]
+fn nothing_to_revalidate_without_either_header() {
+    assert!(Validators::from_headers(&HeaderMap::new(), "{}".to_string()).is_none());
+}
+
+#[test]
+fn request_carries_the_conditional_headers() {
+    let validators = Validators {
+        etag: Some("W/\"abc\"".to_string()),
+        last_modified: None,
+        payload: "{}".to_string(),
+    };
+    let request = validators
+        .apply(reqwest::Client::new().get("https://example.com/"))
+        .build()
+        .unwrap();
+
+    assert_eq!(request.headers()[IF_NONE_MATCH], "W/\"abc\"");
+    assert!(request.headers().get(IF_MODIFIED_SINCE).is_none());
+}
+
+#[test]
+fn validators_survive_the_json_round_trip() {
+    let validators = Validators {
+        etag: None,
+        last_modified: Some(LAST_MODIFIED_AT.to_string()),
+        payload: "[{\"url\":\"a\"}]".to_string(),
+    };
+    let stored = serde_json::to_string(&validators).unwrap();
+    assert_eq!(
+        serde_json::from_str::<Validators>(&stored).unwrap(),
+        validators
+    );
+}
+
+// answers one request, 304 with a newer etag when revalidated, 200 otherwise
+async fn serve_once(listener: TcpListener) -> String {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut request = vec![0; 4096];
+    let read = stream.read(&mut request).await.unwrap();
+    let request = String::from_utf8_lossy(&request[..read]).to_lowercase();
+    let response = if request.contains("if-none-match: \"v1\"") {
+        "HTTP/1.1 304 Not Modified\r\nETag: \"v2\"\r\nContent-Length: 0\r\n\r\n"
+    } else {
+        "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+    };
+    stream.write_all(response.as_bytes()).await.unwrap();
+    request
+}
+
+#[tokio::test]
+async fn not_modified_reuses_the_stored_payload() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/work", listener.local_addr().unwrap());
+    let server = tokio::spawn(serve_once(listener));
+
+    let scraper = generate_scraper(
+        |_: &str| Ok("reparsed".to_string()),
+        RetryPolicy::default(),
+        Arc::new(ParsePool::new(1)),
+        Arc::new(RateLimiter::Local(HostRateLimiter::new(100.0, 10))),
+    );
+    let stored = Validators {
+        etag: Some("\"v1\"".to_string()),
+        last_modified: Some(LAST_MODIFIED_AT.to_string()),
+        payload: "{\"title\":\"stored\"}".to_string(),
+    };
+    let ctx = FetchContext {
+        client: reqwest::Client::new(),
+        proxies: None,
+        sticky_proxy: false,
+        validators: Some(stored.clone()),
+        path_name: "meta".to_string(),
+        request_id: "RJ01234567".to_string(),
+        index: 0,
+        archive: None,
+        fallback_encoding: encoding_rs::UTF_8,
+    };
+
+    let scraped = scraper(ctx, url).await;
+    assert!(server.await.unwrap().contains("if-modified-since"));
+    assert_eq!(scraped.result.unwrap(), stored.payload);
+    assert!(scraped.meta.not_modified);
+    assert_eq!(scraped.meta.status, Some(304));
+    // stored again, with the etag of the 304
+    assert_eq!(
+        scraped.validators,
+        Some(Validators {
+            etag: Some("\"v2\"".to_string()),
+            ..stored
+        })
+    );
+}
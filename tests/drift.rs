@@ -0,0 +1,139 @@
+use rust::{
+    redis_communication::RedisResponse,
+    serv_engine::drift::{DriftChange, DriftConfig, DriftSignal, DriftTracker},
+};
+
+fn tracker() -> DriftTracker {
+    DriftTracker::new(
+        "meta".to_string(),
+        DriftConfig {
+            window: 10,
+            min_samples: 4,
+            empty_rate_threshold: 0.5,
+            error_rate_threshold: 0.3,
+            fields: vec!["cv".to_string(), "time_table".to_string()],
+        },
+    )
+}
+
+fn parsed(payload: serde_json::Value) -> RedisResponse {
+    RedisResponse {
+        error: None,
+        error_code: None,
+        payload: Some(payload.to_string()),
+        index: 0,
+        meta: None,
+    }
+}
+
+fn failed(code: &str) -> RedisResponse {
+    RedisResponse {
+        error: Some(code.to_string()),
+        error_code: Some(code.to_string()),
+        payload: None,
+        index: 0,
+        meta: None,
+    }
+}
+
+#[test]
+fn empty_fields_raise_after_min_samples() {
+    let mut tracker = tracker();
+    let full = serde_json::json!({ "cv": [{ "name": "a" }], "time_table": [{ "index": 0 }] });
+    let no_cv = serde_json::json!({ "cv": [], "time_table": [{ "index": 0 }] });
+
+    assert!(tracker.observe(&parsed(no_cv.clone())).is_empty());
+    assert!(tracker.observe(&parsed(no_cv.clone())).is_empty());
+    assert!(tracker.observe(&parsed(full.clone())).is_empty());
+    // 3 / 4 parses without cv
+    assert_eq!(
+        tracker.observe(&parsed(no_cv.clone())),
+        vec![DriftChange {
+            signal: DriftSignal::EmptyField("cv".to_string()),
+            rate: 0.75,
+            drifting: true,
+        }]
+    );
+    // raised once, not on every scrape
+    assert!(tracker.observe(&parsed(no_cv)).is_empty());
+
+    // cleared at 4 / 8, the threshold is exclusive
+    for _ in 0..2 {
+        assert!(tracker.observe(&parsed(full.clone())).is_empty());
+    }
+    assert_eq!(
+        tracker.observe(&parsed(full)),
+        vec![DriftChange {
+            signal: DriftSignal::EmptyField("cv".to_string()),
+            rate: 0.5,
+            drifting: false,
+        }]
+    );
+}
+
+#[test]
+fn empty_payload_is_always_watched() {
+    let mut tracker = tracker();
+    for _ in 0..3 {
+        tracker.observe(&parsed(serde_json::json!([])));
+    }
+
+    let changes = tracker.observe(&parsed(serde_json::json!([])));
+    let signals: Vec<_> = changes.into_iter().map(|change| change.signal).collect();
+    assert_eq!(
+        signals,
+        vec![
+            DriftSignal::EmptyField("payload".to_string()),
+            DriftSignal::EmptyField("cv".to_string()),
+            DriftSignal::EmptyField("time_table".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn error_codes_are_rated_over_all_scrapes() {
+    let mut tracker = tracker();
+    let full = serde_json::json!({ "cv": [{ "name": "a" }], "time_table": [{ "index": 0 }] });
+
+    tracker.observe(&parsed(full.clone()));
+    tracker.observe(&failed("main_not_found"));
+    tracker.observe(&parsed(full.clone()));
+    // 2 / 4
+    assert_eq!(
+        tracker.observe(&failed("main_not_found")),
+        vec![DriftChange {
+            signal: DriftSignal::ErrorCode("main_not_found".to_string()),
+            rate: 0.5,
+            drifting: true,
+        }]
+    );
+
+    // cleared as good parses dilute the window, 2 / 7
+    let mut cleared = vec![];
+    for _ in 0..10 {
+        cleared.extend(tracker.observe(&parsed(full.clone())));
+    }
+    assert_eq!(
+        cleared,
+        vec![DriftChange {
+            signal: DriftSignal::ErrorCode("main_not_found".to_string()),
+            rate: 2.0 / 7.0,
+            drifting: false,
+        }]
+    );
+}
+
+#[test]
+fn window_must_hold_min_samples() {
+    let config = |window, min_samples| DriftConfig {
+        window,
+        min_samples,
+        empty_rate_threshold: 0.5,
+        error_rate_threshold: 0.3,
+        fields: vec![],
+    };
+    assert!(config(10, 4).validate().is_ok());
+    assert!(config(10, 10).validate().is_ok());
+    assert!(config(0, 0).validate().is_err());
+    assert!(config(4, 10).validate().is_err());
+}
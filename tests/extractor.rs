@@ -0,0 +1,92 @@
+use std::{fs, path::Path};
+
+use rust::parser::{
+    ParserErr,
+    extractor::{Extractor, FieldRule, load_extractor_config},
+};
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name),
+    )
+    .unwrap()
+}
+
+fn extractor_from(fields: serde_json::Value) -> Extractor {
+    let fields: Vec<FieldRule> = serde_json::from_value(fields).unwrap();
+    Extractor::new(&fields).unwrap()
+}
+
+#[test]
+fn example_config_extracts_circle_page() {
+    let config = load_extractor_config(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("extractors.example.json"),
+    )
+    .unwrap();
+    let circle = config
+        .pages
+        .iter()
+        .find(|page| page.name == "circle")
+        .unwrap();
+    let extractor = Extractor::new(&circle.fields).unwrap();
+
+    let result: serde_json::Value =
+        serde_json::from_str(&extractor.extract(&fixture("circle/basic.html")).unwrap()).unwrap();
+
+    assert_eq!(
+        result,
+        serde_json::json!({
+            "id": "circle-a",
+            "name": "サークルA",
+            "work_count": "12",
+            "works": [
+                {
+                    "id": "rj01234567",
+                    "title": "癒やしの耳かき屋さん",
+                    "url": "https://example.com/rj01234567/",
+                },
+                {
+                    "id": "rj01234568",
+                    "title": "添い寝のおはなし",
+                    "url": "https://example.com/rj01234568/",
+                },
+            ],
+        })
+    );
+}
+
+#[test]
+fn missing_optional_fields_are_null_or_empty() {
+    let extractor = extractor_from(serde_json::json!([
+        { "name": "price", "selector": "span.price" },
+        { "name": "samples", "selector": "div.sample img", "attr": "src", "multiple": true },
+    ]));
+
+    let result: serde_json::Value =
+        serde_json::from_str(&extractor.extract(&fixture("circle/basic.html")).unwrap()).unwrap();
+
+    assert_eq!(result, serde_json::json!({ "price": null, "samples": [] }));
+}
+
+#[test]
+fn missing_required_field_fails() {
+    let extractor = extractor_from(serde_json::json!([
+        { "name": "price", "selector": "span.price", "required": true },
+    ]));
+
+    assert!(matches!(
+        extractor.extract(&fixture("circle/basic.html")),
+        Err(ParserErr::RequiredFieldMissing(field)) if field == "price"
+    ));
+}
+
+#[test]
+fn invalid_selector_is_rejected() {
+    let fields: Vec<FieldRule> =
+        serde_json::from_value(serde_json::json!([{ "name": "broken", "selector": "div[" }]))
+            .unwrap();
+
+    assert!(Extractor::new(&fields).is_err());
+}
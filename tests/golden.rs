@@ -0,0 +1,106 @@
+// every tests/fixtures/<page>/<name>.html has its expected output in <name>.json,
+// checked against each enabled backend
+// after an intended change to a parser, rewrite them with
+// `UPDATE_GOLDEN=1 cargo test --test golden` and review the diff
+mod common;
+
+use std::fs;
+
+use rust::parser::ParserErr;
+
+// errors are recorded by their code, e.g. { "error": "tag_list_not_found" }
+fn snapshot(result: Result<String, ParserErr>) -> serde_json::Value {
+    match result {
+        Ok(payload) => serde_json::from_str(&payload)
+            .unwrap_or_else(|e| panic!("parser returned invalid json: {e}\n{payload}")),
+        Err(e) => serde_json::json!({ "error": e.code() }),
+    }
+}
+
+fn assert_golden(dir: &str, parser: impl Fn(&str) -> Result<String, ParserErr>) {
+    let update = std::env::var("UPDATE_GOLDEN").is_ok();
+
+    let mut mismatched = vec![];
+    common::for_each_fixture(dir, |path, html| {
+        let actual = snapshot(parser(html));
+        let expected_path = path.with_extension("json");
+
+        if update {
+            let mut pretty = serde_json::to_string_pretty(&actual).unwrap();
+            pretty.push('\n');
+            fs::write(&expected_path, pretty).unwrap();
+            return;
+        }
+
+        let expected: serde_json::Value = match fs::read_to_string(&expected_path) {
+            Ok(expected) => serde_json::from_str(&expected).unwrap(),
+            Err(_) => panic!("missing expected output {}", expected_path.display()),
+        };
+        if actual != expected {
+            mismatched.push(format!(
+                "{}\nexpected: {expected}\n  actual: {actual}",
+                path.display()
+            ));
+        }
+    });
+
+    assert!(
+        mismatched.is_empty(),
+        "golden mismatch (UPDATE_GOLDEN=1 to accept):\n{}",
+        mismatched.join("\n")
+    );
+}
+
+#[cfg(feature = "ffi-parser")]
+mod ffi_backend {
+    use rust::parser::{ffi_parser_factory, find_detail, find_meta, max_idx_finder, update_tag};
+
+    use super::assert_golden;
+
+    #[test]
+    fn meta_golden() {
+        assert_golden("meta", ffi_parser_factory(find_meta));
+    }
+
+    #[test]
+    fn detail_golden() {
+        assert_golden("detail", ffi_parser_factory(find_detail));
+    }
+
+    #[test]
+    fn tag_golden() {
+        assert_golden("tag", ffi_parser_factory(update_tag));
+    }
+
+    #[test]
+    fn idx_golden() {
+        assert_golden("idx", max_idx_finder);
+    }
+}
+
+#[cfg(feature = "rust-parser")]
+mod rust_backend {
+    use rust::parser::rust_parser::{find_detail, find_max_idx, find_meta, update_tag};
+
+    use super::assert_golden;
+
+    #[test]
+    fn meta_golden() {
+        assert_golden("meta", find_meta);
+    }
+
+    #[test]
+    fn detail_golden() {
+        assert_golden("detail", find_detail);
+    }
+
+    #[test]
+    fn tag_golden() {
+        assert_golden("tag", update_tag);
+    }
+
+    #[test]
+    fn idx_golden() {
+        assert_golden("idx", find_max_idx);
+    }
+}
@@ -0,0 +1,41 @@
+use rust::scraper::{HttpClientConfig, HttpClientErr, parse_header_list};
+
+#[test]
+fn header_list_is_split_on_pipes() {
+    assert_eq!(
+        parse_header_list("Referer: https://example.com/ | X-Requested-With:fetch|"),
+        Some(vec![
+            ("Referer".to_string(), "https://example.com/".to_string()),
+            ("X-Requested-With".to_string(), "fetch".to_string()),
+        ])
+    );
+    assert_eq!(parse_header_list(""), Some(vec![]));
+    assert_eq!(parse_header_list("Referer"), None);
+    assert_eq!(parse_header_list(": value"), None);
+}
+
+#[test]
+fn invalid_header_fails_the_build() {
+    let config = HttpClientConfig {
+        default_headers: vec![("Bad Name".to_string(), "value".to_string())],
+        ..Default::default()
+    };
+    assert!(matches!(
+        config.build(),
+        Err(HttpClientErr::InvalidHeaderName(_))
+    ));
+
+    let config = HttpClientConfig {
+        accept_language: Some("ja\n".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        config.build(),
+        Err(HttpClientErr::InvalidHeaderValue(_))
+    ));
+}
+
+#[test]
+fn default_config_builds() {
+    assert!(HttpClientConfig::default().build().is_ok());
+}
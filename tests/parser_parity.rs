@@ -0,0 +1,63 @@
+#![cfg(all(feature = "ffi-parser", feature = "rust-parser"))]
+// run with `cargo test --features rust-parser`
+mod common;
+
+use rust::parser::{
+    ParserErr, ffi_parser_factory, find_detail, find_meta, max_idx_finder, rust_parser, update_tag,
+};
+
+fn normalize(result: Result<String, ParserErr>) -> Result<serde_json::Value, String> {
+    match result {
+        Ok(payload) => {
+            Ok(serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload)))
+        }
+        Err(e) => Err(format!("{e:?}")),
+    }
+}
+
+fn assert_parity(
+    dir: &str,
+    ffi: impl Fn(&str) -> Result<String, ParserErr>,
+    rust: impl Fn(&str) -> Result<String, ParserErr>,
+) {
+    common::for_each_fixture(dir, |path, html| {
+        assert_eq!(
+            normalize(ffi(html)),
+            normalize(rust(html)),
+            "backends disagree on {}",
+            path.display()
+        );
+    });
+}
+
+#[test]
+fn meta_parity() {
+    assert_parity(
+        "meta",
+        ffi_parser_factory(find_meta),
+        rust_parser::find_meta,
+    );
+}
+
+#[test]
+fn detail_parity() {
+    assert_parity(
+        "detail",
+        ffi_parser_factory(find_detail),
+        rust_parser::find_detail,
+    );
+}
+
+#[test]
+fn tag_parity() {
+    assert_parity(
+        "tag",
+        ffi_parser_factory(update_tag),
+        rust_parser::update_tag,
+    );
+}
+
+#[test]
+fn idx_parity() {
+    assert_parity("idx", max_idx_finder, rust_parser::find_max_idx);
+}
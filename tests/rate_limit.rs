@@ -0,0 +1,75 @@
+use std::{sync::Arc, time::Duration};
+
+use rust::scraper::{ClusterRateLimiter, HostRateLimiter, RateLimiter, host_of};
+
+#[test]
+fn burst_is_free_then_requests_queue_up() {
+    let limiter = HostRateLimiter::new(10.0, 3);
+    for _ in 0..3 {
+        assert_eq!(limiter.reserve("example.com"), Duration::ZERO);
+    }
+
+    // 100ms per token at 10 rps, each caller waits behind the previous one
+    let first = limiter.reserve("example.com");
+    let second = limiter.reserve("example.com");
+    assert!(first > Duration::from_millis(90) && first <= Duration::from_millis(100));
+    assert!(second > Duration::from_millis(190) && second <= Duration::from_millis(200));
+}
+
+#[test]
+fn hosts_have_their_own_bucket() {
+    let limiter = HostRateLimiter::new(1.0, 1);
+    assert_eq!(limiter.reserve("example.com"), Duration::ZERO);
+    assert_eq!(limiter.reserve("example.org"), Duration::ZERO);
+    assert!(limiter.reserve("example.com") > Duration::ZERO);
+}
+
+#[test]
+fn zero_rps_disables_the_limiter() {
+    let limiter = HostRateLimiter::new(0.0, 1);
+    for _ in 0..10 {
+        assert_eq!(limiter.reserve("example.com"), Duration::ZERO);
+    }
+}
+
+#[tokio::test]
+async fn acquire_sleeps_for_the_reservation() {
+    let limiter = HostRateLimiter::new(2.0, 1);
+    let started = std::time::Instant::now();
+    limiter.acquire("example.com").await;
+    limiter.acquire("example.com").await;
+    assert!(started.elapsed() >= Duration::from_millis(500));
+}
+
+#[test]
+fn host_is_taken_from_the_url() {
+    assert_eq!(
+        host_of("https://Example.com:8080/rj01234567/"),
+        "example.com"
+    );
+    assert_eq!(host_of("not a url"), "");
+}
+
+#[tokio::test]
+async fn cluster_limiter_falls_back_while_redis_is_unreachable() {
+    let manager = bb8_redis::RedisConnectionManager::new("redis://127.0.0.1:1").unwrap();
+    let pool = Arc::new(
+        bb8::Pool::builder()
+            .connection_timeout(Duration::from_secs(5))
+            .build_unchecked(manager),
+    );
+    let limiter = RateLimiter::Cluster(ClusterRateLimiter::new(
+        pool,
+        "rate_limit".to_string(),
+        100.0,
+        10,
+        Duration::from_millis(50),
+        HostRateLimiter::new(2.0, 1),
+    ));
+
+    // the fallback's 2 rps applies, not the cluster's 100
+    let started = std::time::Instant::now();
+    limiter.acquire("example.com").await;
+    limiter.acquire("example.com").await;
+    assert!(started.elapsed() >= Duration::from_millis(500));
+}
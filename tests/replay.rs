@@ -0,0 +1,129 @@
+use std::time::Duration;
+
+use rust::{
+    scraper::{ArchiveHeader, ArchiveRecord, ArchiveSink, ArchiveStore},
+    serv_engine::replay::{ReplayArgs, ReplayFilter, parse_date},
+};
+
+fn args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn header(path: &str, id: &str, fetched_at: u64) -> ArchiveHeader {
+    ArchiveHeader {
+        path: path.to_string(),
+        id: id.to_string(),
+        index: 0,
+        identifier: id.to_lowercase(),
+        url: format!("https://example.com/work/=/product_id/{id}.html"),
+        fetched_at,
+        status: 200,
+        headers: vec![],
+    }
+}
+
+#[test]
+fn dates_are_utc_millis() {
+    assert_eq!(parse_date("1970-01-01"), Some(0));
+    assert_eq!(parse_date("2024-02-29"), Some(1_709_164_800_000));
+    assert_eq!(parse_date("2024-02-29T12:30:00Z"), Some(1_709_209_800_000));
+    assert_eq!(parse_date("2024-13-01"), None);
+    assert_eq!(parse_date("yesterday"), None);
+}
+
+#[test]
+fn args_become_a_filter() {
+    let parsed = ReplayArgs::parse(&args(&[
+        "--path",
+        "meta",
+        "--path",
+        "detail",
+        "--since",
+        "2024-01-01",
+        "--until",
+        "2024-02-01",
+        "--id",
+        "RJ01234567",
+        "--job-id",
+        "fix-genre",
+    ]))
+    .unwrap();
+
+    assert_eq!(parsed.filter.paths, vec!["meta", "detail"]);
+    assert_eq!(parsed.filter.since, parse_date("2024-01-01"));
+    assert_eq!(parsed.filter.until, parse_date("2024-02-01"));
+    assert_eq!(parsed.filter.ids, vec!["RJ01234567"]);
+    assert_eq!(parsed.job_id.as_deref(), Some("fix-genre"));
+
+    assert!(ReplayArgs::parse(&args(&["--since", "soon"])).is_err());
+    assert!(ReplayArgs::parse(&args(&["--path"])).is_err());
+    assert!(ReplayArgs::parse(&args(&["--force", "true"])).is_err());
+}
+
+#[test]
+fn ids_match_request_id_identifier_or_url() {
+    let filter = ReplayFilter {
+        ids: vec!["RJ01234567".to_string()],
+        ..Default::default()
+    };
+    assert!(filter.matches(&header("meta", "RJ01234567", 0)));
+    assert!(!filter.matches(&header("meta", "RJ07654321", 0)));
+
+    let by_url = ReplayFilter {
+        ids: vec![header("meta", "RJ07654321", 0).url],
+        ..Default::default()
+    };
+    assert!(by_url.matches(&header("meta", "RJ07654321", 0)));
+    assert!(ReplayFilter::default().matches(&header("meta", "RJ07654321", 0)));
+}
+
+#[tokio::test]
+async fn disk_archive_lists_by_path_and_date() {
+    let dir = std::env::temp_dir().join(format!("replay-list-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sink = ArchiveSink {
+        store: ArchiveStore::Disk { dir: dir.clone() },
+        retention: Duration::from_secs(60),
+        level: 3,
+    };
+    for (path, id, fetched_at) in [
+        ("meta", "RJ01234567", 1_000),
+        ("meta", "RJ01234567", 2_000),
+        ("detail", "RJ07654321", 3_000),
+    ] {
+        let record = ArchiveRecord {
+            header: header(path, id, fetched_at),
+            body: b"<html></html>".to_vec(),
+        };
+        sink.write(&record).await.unwrap();
+    }
+
+    let mut meta = sink.list(&["meta".to_string()]).await.unwrap();
+    meta.sort_by_key(|entry| entry.fetched_at);
+    assert_eq!(
+        meta.iter()
+            .map(|entry| entry.fetched_at)
+            .collect::<Vec<_>>(),
+        vec![1_000, 2_000]
+    );
+    assert_eq!(sink.list(&[]).await.unwrap().len(), 3);
+
+    let filter = ReplayFilter {
+        since: Some(1_500),
+        until: Some(3_000),
+        ..Default::default()
+    };
+    let kept: Vec<_> = sink
+        .list(&[])
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|entry| filter.matches_entry(entry))
+        .collect();
+    assert_eq!(kept.len(), 1);
+
+    let record = sink.read(&kept[0]).await.unwrap().unwrap();
+    assert_eq!(record.header, header("meta", "RJ01234567", 2_000));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,85 @@
+use std::time::{Duration, SystemTime};
+
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, HeaderValue, RETRY_AFTER},
+};
+use rust::scraper::{RetryPolicy, StatusClass, classify, parse_retry_after};
+
+fn policy() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 4,
+        init_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(1000),
+        jitter: 0.5,
+        max_retry_after: Duration::from_secs(60),
+    }
+}
+
+#[test]
+fn statuses_are_classified() {
+    assert_eq!(classify(StatusCode::OK), StatusClass::Success);
+    assert_eq!(
+        classify(StatusCode::TOO_MANY_REQUESTS),
+        StatusClass::Retryable
+    );
+    assert_eq!(
+        classify(StatusCode::SERVICE_UNAVAILABLE),
+        StatusClass::Retryable
+    );
+    assert_eq!(classify(StatusCode::NOT_FOUND), StatusClass::Permanent);
+    assert_eq!(classify(StatusCode::GONE), StatusClass::Permanent);
+}
+
+#[test]
+fn backoff_grows_within_jitter_and_cap() {
+    let policy = policy();
+    for _ in 0..100 {
+        let first = policy.backoff(1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(150));
+        let third = policy.backoff(3);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(600));
+        assert!(policy.backoff(30) <= Duration::from_millis(1000));
+    }
+
+    let exact = RetryPolicy {
+        jitter: 0.0,
+        ..policy
+    };
+    assert_eq!(exact.backoff(2), Duration::from_millis(200));
+}
+
+#[test]
+fn retry_after_is_honored() {
+    let policy = policy();
+    assert_eq!(
+        policy.next_delay(1, Some(Duration::from_secs(5))),
+        Some(Duration::from_secs(5))
+    );
+    // too long to hold the permit
+    assert_eq!(policy.next_delay(1, Some(Duration::from_secs(61))), None);
+    assert_eq!(policy.next_delay(4, None), None);
+    assert!(policy.next_delay(3, None).is_some());
+}
+
+#[test]
+fn retry_after_header_is_parsed() {
+    let now = httpdate::parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT").unwrap();
+    let with = |value: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(value));
+        parse_retry_after(&headers, now)
+    };
+
+    assert_eq!(with("120"), Some(Duration::from_secs(120)));
+    assert_eq!(
+        with("Sun, 06 Nov 1994 08:50:07 GMT"),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(with("Sun, 06 Nov 1994 08:00:00 GMT"), Some(Duration::ZERO));
+    assert_eq!(with("soon"), None);
+    assert_eq!(
+        parse_retry_after(&HeaderMap::new(), SystemTime::now()),
+        None
+    );
+}
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use rust::{
+    redis_communication::RedisResponse,
+    scraper::{HostRateLimiter, RobotsRules},
+    serv_engine::{Delivery, ScrapeResultItem},
+};
+
+const ROBOTS_TXT: &str = "
+# comments are ignored
+User-agent: *
+Disallow: /search
+Allow: /search/about
+Disallow: /*.json$
+Crawl-delay: 2
+
+User-agent: rust-scraper
+User-agent: other-bot
+Disallow: /private/
+Allow: /private/public
+Crawl-delay: 0.5
+";
+
+#[test]
+fn named_group_wins_over_the_wildcard_one() {
+    let rules = RobotsRules::parse(ROBOTS_TXT, "Rust-Scraper");
+    assert!(!rules.is_allowed("/private/a"));
+    assert!(rules.is_allowed("/private/public"));
+    // only the named group applies
+    assert!(rules.is_allowed("/search?q=a"));
+    assert_eq!(rules.crawl_delay, Some(Duration::from_millis(500)));
+}
+
+#[test]
+fn wildcard_group_applies_to_other_agents() {
+    let rules = RobotsRules::parse(ROBOTS_TXT, "unknown-bot");
+    assert!(!rules.is_allowed("/search?q=a"));
+    // longest match
+    assert!(rules.is_allowed("/search/about"));
+    assert!(!rules.is_allowed("/api/rj01234567.json"));
+    assert!(rules.is_allowed("/api/rj01234567.json?v=1"));
+    assert!(rules.is_allowed("/rj01234567/"));
+    assert_eq!(rules.crawl_delay, Some(Duration::from_secs(2)));
+}
+
+#[test]
+fn missing_or_empty_robots_allows_everything() {
+    let rules = RobotsRules::parse("", "rust-scraper");
+    assert!(rules.is_allowed("/anything"));
+    assert_eq!(rules.crawl_delay, None);
+
+    let rules = RobotsRules::parse("User-agent: *\nDisallow:\n", "rust-scraper");
+    assert!(rules.is_allowed("/anything"));
+}
+
+#[test]
+fn allow_wins_a_tie_and_robots_txt_is_always_allowed() {
+    let rules = RobotsRules::parse(
+        "User-agent: *\nDisallow: /page\nAllow: /page\nDisallow: /\n",
+        "rust-scraper",
+    );
+    assert!(rules.is_allowed("/page/2"));
+    assert!(!rules.is_allowed("/other"));
+    assert!(rules.is_allowed("/robots.txt"));
+}
+
+#[test]
+fn crawl_delay_lowers_the_host_rate() {
+    let limiter = HostRateLimiter::new(10.0, 5);
+    limiter.set_crawl_delay("example.com", Duration::from_secs(1));
+
+    // no burst, one request per second
+    assert_eq!(limiter.reserve("example.com"), Duration::ZERO);
+    assert!(limiter.reserve("example.com") > Duration::from_millis(900));
+    assert_eq!(limiter.reserve("example.org"), Duration::ZERO);
+    assert_eq!(limiter.reserve("example.org"), Duration::ZERO);
+}
+
+fn result(status_update_url: Option<&str>, robots_disallowed: bool) -> ScrapeResultItem {
+    ScrapeResultItem {
+        id: "RJ01234567".to_string(),
+        job_id: "job".to_string(),
+        status_update_url: status_update_url.map(str::to_string),
+        response: RedisResponse {
+            error: None,
+            error_code: None,
+            payload: None,
+            index: 0,
+            meta: None,
+        },
+        validators: None,
+        robots_disallowed,
+    }
+}
+
+#[test]
+fn disallowed_items_are_reported_but_not_recorded() {
+    assert_eq!(result(None, true).delivery(), Delivery::Report);
+    assert_eq!(result(None, false).delivery(), Delivery::Skip);
+    assert_eq!(
+        result(Some("https://example.com/work"), false).delivery(),
+        Delivery::Record
+    );
+}
@@ -0,0 +1,22 @@
+use std::time::{Duration, Instant};
+
+use rust::parser::{ParserErr, worker::WorkerPool};
+
+#[test]
+fn a_child_that_never_reads_is_timed_out_and_replaced() {
+    // never reads stdin, a body past the pipe buffer blocks the write
+    let pool = WorkerPool::with_command(
+        "stuck",
+        "sleep".into(),
+        vec!["30".to_string()],
+        1,
+        Duration::from_millis(200),
+    );
+    let html = "<html>".repeat(64 * 1024);
+
+    for _ in 0..2 {
+        let started = Instant::now();
+        assert!(matches!(pool.parse(&html), Err(ParserErr::WorkerTimeout)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}